@@ -0,0 +1,10 @@
+-- Drop triggers
+DROP TRIGGER IF EXISTS bookmarks_fts_tags_after_delete;
+DROP TRIGGER IF EXISTS bookmarks_fts_tags_after_update;
+DROP TRIGGER IF EXISTS bookmarks_fts_tags_after_insert;
+DROP TRIGGER IF EXISTS bookmarks_fts_after_delete;
+DROP TRIGGER IF EXISTS bookmarks_fts_after_update;
+DROP TRIGGER IF EXISTS bookmarks_fts_after_insert;
+
+-- Drop tables
+DROP TABLE IF EXISTS bookmarks_fts;
@@ -0,0 +1,59 @@
+-- Full-text index over bookmark titles, links and their tags.
+-- The rowid of every row is the id of the bookmark it indexes.
+CREATE VIRTUAL TABLE bookmarks_fts USING fts5(
+  title,
+  link,
+  tags,
+  tokenize = 'unicode61 remove_diacritics 2',
+  prefix = '2 3'
+);
+
+-- Index the bookmarks that already exist
+INSERT INTO bookmarks_fts (rowid, title, link, tags)
+SELECT
+  bookmarks_table.id,
+  bookmarks_table.title,
+  bookmarks_table.link,
+  (SELECT group_concat(tag_name, ' ') FROM tags_table WHERE tags_table.bookmark_id = bookmarks_table.id)
+FROM bookmarks_table;
+
+-- Keep the index in sync with bookmarks_table
+CREATE TRIGGER bookmarks_fts_after_insert AFTER INSERT ON bookmarks_table
+BEGIN
+  INSERT INTO bookmarks_fts (rowid, title, link, tags) VALUES (new.id, new.title, new.link, '');
+END;
+
+CREATE TRIGGER bookmarks_fts_after_update AFTER UPDATE OF title, link ON bookmarks_table
+BEGIN
+  UPDATE bookmarks_fts SET title = new.title, link = new.link WHERE rowid = new.id;
+END;
+
+CREATE TRIGGER bookmarks_fts_after_delete AFTER DELETE ON bookmarks_table
+BEGIN
+  DELETE FROM bookmarks_fts WHERE rowid = old.id;
+END;
+
+-- Keep the aggregated tags column in sync with tags_table
+CREATE TRIGGER bookmarks_fts_tags_after_insert AFTER INSERT ON tags_table
+BEGIN
+  UPDATE bookmarks_fts
+  SET tags = (SELECT group_concat(tag_name, ' ') FROM tags_table WHERE bookmark_id = new.bookmark_id)
+  WHERE rowid = new.bookmark_id;
+END;
+
+CREATE TRIGGER bookmarks_fts_tags_after_update AFTER UPDATE ON tags_table
+BEGIN
+  UPDATE bookmarks_fts
+  SET tags = (SELECT group_concat(tag_name, ' ') FROM tags_table WHERE bookmark_id = old.bookmark_id)
+  WHERE rowid = old.bookmark_id;
+  UPDATE bookmarks_fts
+  SET tags = (SELECT group_concat(tag_name, ' ') FROM tags_table WHERE bookmark_id = new.bookmark_id)
+  WHERE rowid = new.bookmark_id;
+END;
+
+CREATE TRIGGER bookmarks_fts_tags_after_delete AFTER DELETE ON tags_table
+BEGIN
+  UPDATE bookmarks_fts
+  SET tags = (SELECT group_concat(tag_name, ' ') FROM tags_table WHERE bookmark_id = old.bookmark_id)
+  WHERE rowid = old.bookmark_id;
+END;
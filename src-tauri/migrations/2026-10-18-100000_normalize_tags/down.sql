@@ -0,0 +1,45 @@
+-- Restore the per bookmark tags table
+CREATE TABLE tags_table (
+  id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  bookmark_id INTEGER NOT NULL,
+  tag_name TEXT NOT NULL,
+  FOREIGN KEY (bookmark_id) REFERENCES bookmarks_table(id) ON DELETE CASCADE
+);
+
+INSERT INTO tags_table (bookmark_id, tag_name)
+SELECT bookmark_tags.bookmark_id, tags.name
+FROM bookmark_tags
+INNER JOIN tags ON tags.id = bookmark_tags.tag_id;
+
+-- Drop triggers
+DROP TRIGGER IF EXISTS bookmarks_fts_tags_after_rename;
+DROP TRIGGER IF EXISTS bookmarks_fts_tags_after_delete;
+DROP TRIGGER IF EXISTS bookmarks_fts_tags_after_insert;
+
+-- Drop tables
+DROP TABLE IF EXISTS bookmark_tags;
+DROP TABLE IF EXISTS tags;
+
+CREATE TRIGGER bookmarks_fts_tags_after_insert AFTER INSERT ON tags_table
+BEGIN
+  UPDATE bookmarks_fts
+  SET tags = (SELECT group_concat(tag_name, ' ') FROM tags_table WHERE bookmark_id = new.bookmark_id)
+  WHERE rowid = new.bookmark_id;
+END;
+
+CREATE TRIGGER bookmarks_fts_tags_after_update AFTER UPDATE ON tags_table
+BEGIN
+  UPDATE bookmarks_fts
+  SET tags = (SELECT group_concat(tag_name, ' ') FROM tags_table WHERE bookmark_id = old.bookmark_id)
+  WHERE rowid = old.bookmark_id;
+  UPDATE bookmarks_fts
+  SET tags = (SELECT group_concat(tag_name, ' ') FROM tags_table WHERE bookmark_id = new.bookmark_id)
+  WHERE rowid = new.bookmark_id;
+END;
+
+CREATE TRIGGER bookmarks_fts_tags_after_delete AFTER DELETE ON tags_table
+BEGIN
+  UPDATE bookmarks_fts
+  SET tags = (SELECT group_concat(tag_name, ' ') FROM tags_table WHERE bookmark_id = old.bookmark_id)
+  WHERE rowid = old.bookmark_id;
+END;
@@ -0,0 +1,68 @@
+-- One row per distinct tag, with its metadata
+CREATE TABLE tags (
+  id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  name TEXT NOT NULL UNIQUE,
+  colour TEXT,
+  description TEXT,
+  created_at BIGINT NOT NULL DEFAULT (strftime('%s','now'))
+);
+
+-- Which bookmark carries which tag, each pair at most once
+CREATE TABLE bookmark_tags (
+  bookmark_id INTEGER NOT NULL,
+  tag_id INTEGER NOT NULL,
+  PRIMARY KEY (bookmark_id, tag_id),
+  FOREIGN KEY (bookmark_id) REFERENCES bookmarks_table(id) ON DELETE CASCADE,
+  FOREIGN KEY (tag_id) REFERENCES tags(id) ON DELETE CASCADE
+);
+
+CREATE INDEX bookmark_tags_tag_id ON bookmark_tags (tag_id);
+
+-- Move the existing tags over
+INSERT INTO tags (name)
+SELECT DISTINCT tag_name FROM tags_table WHERE tag_name <> '';
+
+INSERT OR IGNORE INTO bookmark_tags (bookmark_id, tag_id)
+SELECT tags_table.bookmark_id, tags.id
+FROM tags_table
+INNER JOIN tags ON tags.name = tags_table.tag_name;
+
+-- Replace the full-text triggers that watched tags_table
+DROP TRIGGER IF EXISTS bookmarks_fts_tags_after_delete;
+DROP TRIGGER IF EXISTS bookmarks_fts_tags_after_update;
+DROP TRIGGER IF EXISTS bookmarks_fts_tags_after_insert;
+
+DROP TABLE tags_table;
+
+CREATE TRIGGER bookmarks_fts_tags_after_insert AFTER INSERT ON bookmark_tags
+BEGIN
+  UPDATE bookmarks_fts
+  SET tags = (
+    SELECT group_concat(tags.name, ' ') FROM bookmark_tags
+    INNER JOIN tags ON tags.id = bookmark_tags.tag_id
+    WHERE bookmark_tags.bookmark_id = new.bookmark_id
+  )
+  WHERE rowid = new.bookmark_id;
+END;
+
+CREATE TRIGGER bookmarks_fts_tags_after_delete AFTER DELETE ON bookmark_tags
+BEGIN
+  UPDATE bookmarks_fts
+  SET tags = (
+    SELECT group_concat(tags.name, ' ') FROM bookmark_tags
+    INNER JOIN tags ON tags.id = bookmark_tags.tag_id
+    WHERE bookmark_tags.bookmark_id = old.bookmark_id
+  )
+  WHERE rowid = old.bookmark_id;
+END;
+
+CREATE TRIGGER bookmarks_fts_tags_after_rename AFTER UPDATE OF name ON tags
+BEGIN
+  UPDATE bookmarks_fts
+  SET tags = (
+    SELECT group_concat(tags.name, ' ') FROM bookmark_tags
+    INNER JOIN tags ON tags.id = bookmark_tags.tag_id
+    WHERE bookmark_tags.bookmark_id = bookmarks_fts.rowid
+  )
+  WHERE rowid IN (SELECT bookmark_id FROM bookmark_tags WHERE tag_id = new.id);
+END;
@@ -0,0 +1,30 @@
+-- Tags form a tree through their names ("dev/rust/async"), parent_id points at the
+-- tag one level up and is NULL for top level tags
+ALTER TABLE tags ADD COLUMN parent_id INTEGER;
+
+CREATE INDEX tags_parent_id ON tags (parent_id);
+
+-- Create the ancestors of existing nested tags that don't exist on their own yet
+WITH RECURSIVE ancestors(path, rest) AS (
+  SELECT '', name FROM tags WHERE instr(name, '/') > 0
+  UNION ALL
+  SELECT
+    CASE path
+      WHEN '' THEN substr(rest, 1, instr(rest, '/') - 1)
+      ELSE path || '/' || substr(rest, 1, instr(rest, '/') - 1)
+    END,
+    substr(rest, instr(rest, '/') + 1)
+  FROM ancestors
+  WHERE instr(rest, '/') > 0
+)
+INSERT OR IGNORE INTO tags (name)
+SELECT DISTINCT path FROM ancestors WHERE path <> '';
+
+-- Link every tag to its parent
+UPDATE tags
+SET parent_id = (
+  SELECT parent.id FROM tags AS parent
+  WHERE substr(tags.name, 1, length(parent.name) + 1) = parent.name || '/'
+    AND instr(substr(tags.name, length(parent.name) + 2), '/') = 0
+)
+WHERE instr(name, '/') > 0;
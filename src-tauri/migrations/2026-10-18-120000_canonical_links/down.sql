@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS bookmarks_table_canonical_link;
+
+ALTER TABLE bookmarks_table DROP COLUMN canonical_link;
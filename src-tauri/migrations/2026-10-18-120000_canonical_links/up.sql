@@ -0,0 +1,5 @@
+-- Canonical form of the link, used to recognise duplicates.
+-- Existing rows are filled in by the app after migrating, since canonicalization lives in Rust.
+ALTER TABLE bookmarks_table ADD COLUMN canonical_link TEXT NOT NULL DEFAULT '';
+
+CREATE INDEX bookmarks_table_canonical_link ON bookmarks_table (canonical_link);
@@ -0,0 +1,9 @@
+DROP TABLE IF EXISTS import_overwrites;
+
+DROP INDEX IF EXISTS bookmark_tags_import_id;
+DROP INDEX IF EXISTS bookmarks_table_import_id;
+
+ALTER TABLE bookmark_tags DROP COLUMN import_id;
+ALTER TABLE bookmarks_table DROP COLUMN import_id;
+
+DROP TABLE IF EXISTS imports;
@@ -0,0 +1,34 @@
+-- One row per import, so a bad one can be rolled back
+CREATE TABLE imports (
+  id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  source_file TEXT NOT NULL,
+  parser_name TEXT NOT NULL,
+  created_at BIGINT NOT NULL DEFAULT (strftime('%s','now')),
+  inserted INTEGER NOT NULL DEFAULT 0,
+  skipped INTEGER NOT NULL DEFAULT 0,
+  merged INTEGER NOT NULL DEFAULT 0,
+  updated INTEGER NOT NULL DEFAULT 0,
+  failed INTEGER NOT NULL DEFAULT 0
+);
+
+-- The import that created a bookmark, or added a tag to one
+ALTER TABLE bookmarks_table ADD COLUMN import_id INTEGER;
+ALTER TABLE bookmark_tags ADD COLUMN import_id INTEGER;
+
+CREATE INDEX bookmarks_table_import_id ON bookmarks_table (import_id);
+CREATE INDEX bookmark_tags_import_id ON bookmark_tags (import_id);
+
+-- Bookmarks as they were before an import overwrote them with the update duplicate policy.
+-- tags holds a JSON array of tag names.
+CREATE TABLE import_overwrites (
+  import_id INTEGER NOT NULL,
+  bookmark_id INTEGER NOT NULL,
+  title TEXT,
+  link TEXT NOT NULL,
+  icon_link TEXT,
+  created_at BIGINT NOT NULL,
+  canonical_link TEXT NOT NULL,
+  tags TEXT NOT NULL,
+  PRIMARY KEY (import_id, bookmark_id),
+  FOREIGN KEY (import_id) REFERENCES imports(id) ON DELETE CASCADE
+);
@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use diesel::dsl::count;
+use diesel::prelude::*;
+use serde::Serialize;
+use tauri::ipc::Channel;
+use tauri::{AppHandle, Manager};
+
+use crate::commands::{apply_filters, attach_tags, FilterItem};
+use crate::database_cmds::{DbConnection, DbPool};
+use crate::models::Bookmark;
+use crate::pagination::{self, Cursor, SortKey};
+use crate::structs::BookmarkWithTags;
+use crate::utils::broadcast_info;
+
+// Batches the frontend may have received but not yet acknowledged
+const MAX_UNACKED_BATCHES: u64 = 2;
+// A stream whose consumer stops acknowledging (e.g. the window was closed) is dropped after this
+const ACK_TIMEOUT: Duration = Duration::from_secs(60);
+// Largest batch a caller can ask for, the tags of a batch are loaded in one query
+// that has to stay below SQLite's variable limit
+pub const MAX_BATCH_SIZE: i64 = 500;
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BookmarkStreamBatch {
+    pub stream_id: u32,
+    pub batch: u64,
+    pub bookmarks: Vec<BookmarkWithTags>,
+    pub total_count: i64,
+    pub done: bool,
+    // Set on the last batch when the stream ended early, it has no bookmarks then
+    pub error: Option<String>,
+}
+
+pub struct StreamControl {
+    cancelled: AtomicBool,
+    batches: Mutex<BatchCounts>,
+    acked_changed: Condvar,
+}
+
+// Batches sent so far and how many of them the frontend acknowledged
+#[derive(Default)]
+struct BatchCounts {
+    sent: u64,
+    acked: u64,
+}
+
+impl StreamControl {
+    fn new() -> Self {
+        StreamControl {
+            cancelled: AtomicBool::new(false),
+            batches: Mutex::new(BatchCounts::default()),
+            acked_changed: Condvar::new(),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+        self.acked_changed.notify_all();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    // Batches are numbered from 0, acknowledging one acknowledges every batch before it.
+    // Acks for batches that weren't sent yet only count up to the last one sent.
+    pub fn ack(&self, batch: u64) {
+        let mut batches = self.batches.lock().unwrap();
+        batches.acked = batches.acked.max(batch.saturating_add(1)).min(batches.sent);
+        self.acked_changed.notify_all();
+    }
+
+    // Counted before the batch goes out, so an ack arriving right after it is accepted
+    fn sending(&self, batch: u64) {
+        let mut batches = self.batches.lock().unwrap();
+        batches.sent = batches.sent.max(batch.saturating_add(1));
+    }
+
+    fn sent(&self) -> u64 {
+        self.batches.lock().unwrap().sent
+    }
+
+    // Block until at most MAX_UNACKED_BATCHES of the sent batches are unacknowledged.
+    // Returns false when the stream was cancelled or the consumer stopped responding.
+    fn wait_for_ack(&self) -> bool {
+        let deadline = Instant::now() + ACK_TIMEOUT;
+        let mut batches = self.batches.lock().unwrap();
+
+        while batches.sent.saturating_sub(batches.acked) >= MAX_UNACKED_BATCHES {
+            if self.is_cancelled() {
+                return false;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            batches = self
+                .acked_changed
+                .wait_timeout(batches, deadline - now)
+                .unwrap()
+                .0;
+        }
+
+        !self.is_cancelled()
+    }
+}
+
+pub struct BookmarkStreams {
+    streams: HashMap<u32, Arc<StreamControl>>,
+    next_id: u32,
+}
+
+impl BookmarkStreams {
+    pub fn new() -> Self {
+        Self {
+            streams: HashMap::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn open(&mut self) -> (u32, Arc<StreamControl>) {
+        let stream_id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        let control = Arc::new(StreamControl::new());
+        self.streams.insert(stream_id, control.clone());
+        (stream_id, control)
+    }
+
+    pub fn get(&self, stream_id: u32) -> Option<Arc<StreamControl>> {
+        self.streams.get(&stream_id).cloned()
+    }
+
+    pub fn close(&mut self, stream_id: u32) {
+        self.streams.remove(&stream_id);
+    }
+}
+
+pub struct StreamRequest {
+    pub stream_id: u32,
+    pub control: Arc<StreamControl>,
+    pub filters: Vec<FilterItem>,
+    pub sort_keys: Vec<SortKey>,
+    pub batch_size: i64,
+}
+
+// Push every bookmark matching the filters through `channel`, one keyset page per batch.
+// Runs on a blocking thread and only holds a pooled connection while reading a batch.
+pub fn run_stream(
+    app: AppHandle,
+    pool: DbPool,
+    request: StreamRequest,
+    channel: Channel<BookmarkStreamBatch>,
+) {
+    let error = match send_batches(&pool, &request, &channel) {
+        Ok(true) => None,
+        Ok(false) if request.control.is_cancelled() => Some("Stream was cancelled".to_string()),
+        Ok(false) => Some("Stream stopped waiting for the batches to be acknowledged".to_string()),
+        Err(e) => {
+            let error = format!("Error streaming bookmarks: {}", e);
+            broadcast_info("Database Error", &error, log::Level::Error, false);
+            Some(error)
+        }
+    };
+
+    // The consumer waits for a batch marked done, it gets one however the stream ended
+    if let Some(error) = error {
+        let _ = channel.send(BookmarkStreamBatch {
+            stream_id: request.stream_id,
+            batch: request.control.sent(),
+            bookmarks: Vec::new(),
+            total_count: 0,
+            done: true,
+            error: Some(error),
+        });
+    }
+
+    let binding = app.state::<Mutex<BookmarkStreams>>();
+    let mut streams = binding.lock().unwrap();
+    streams.close(request.stream_id);
+}
+
+// Whether every batch was sent, false when the stream was cancelled or not acknowledged
+fn send_batches(
+    pool: &DbPool,
+    request: &StreamRequest,
+    channel: &Channel<BookmarkStreamBatch>,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    use crate::schema::bookmarks_table::dsl::*;
+
+    let total = {
+        let mut conn = pool.get()?;
+        apply_filters(bookmarks_table.into_boxed(), &request.filters, false)?
+            .select(count(id))
+            .first::<i64>(&mut conn)?
+    };
+
+    let mut after: Option<Cursor> = None;
+    let mut batch = 0;
+
+    while !request.control.is_cancelled() {
+        let (bookmarks, has_more) = {
+            let mut conn = pool.get()?;
+            read_batch(
+                &mut conn,
+                &request.filters,
+                &request.sort_keys,
+                &mut after,
+                request.batch_size,
+            )?
+        };
+
+        request.control.sending(batch);
+        channel.send(BookmarkStreamBatch {
+            stream_id: request.stream_id,
+            batch,
+            bookmarks,
+            total_count: total,
+            done: !has_more,
+            error: None,
+        })?;
+        batch += 1;
+
+        if !has_more {
+            return Ok(true);
+        }
+        if !request.control.wait_for_ack() {
+            break;
+        }
+    }
+
+    Ok(false)
+}
+
+// The keyset page after `after` with the tags of its bookmarks, and whether more pages follow.
+// `after` is moved to the last bookmark of the page.
+fn read_batch(
+    conn: &mut DbConnection,
+    filters: &[FilterItem],
+    sort_keys: &[SortKey],
+    after: &mut Option<Cursor>,
+    batch_size: i64,
+) -> Result<(Vec<BookmarkWithTags>, bool), Box<dyn std::error::Error>> {
+    use crate::schema::bookmarks_table::dsl::*;
+
+    let query = apply_filters(bookmarks_table.into_boxed(), filters, false)?;
+    let query =
+        pagination::apply_keyset(query, sort_keys, after.as_ref()).map_err(|e| e.to_string())?;
+
+    let mut bookmarks = query
+        .select(Bookmark::as_select())
+        .limit(batch_size + 1)
+        .load::<Bookmark>(conn)?;
+    let has_more = bookmarks.len() as i64 > batch_size;
+    bookmarks.truncate(batch_size as usize);
+    *after = bookmarks.last().map(|last| Cursor::after(last, sort_keys));
+
+    Ok((attach_tags(conn, bookmarks), has_more))
+}
+
+// Every bookmark matching the filters, read one keyset page at a time so loading
+// their tags stays below SQLite's variable limit
+pub fn read_all(
+    pool: &DbPool,
+    filters: &[FilterItem],
+    sort_keys: &[SortKey],
+    batch_size: i64,
+) -> Result<Vec<BookmarkWithTags>, Box<dyn std::error::Error>> {
+    let mut conn = pool.get()?;
+    let mut after: Option<Cursor> = None;
+    let mut bookmarks: Vec<BookmarkWithTags> = Vec::new();
+
+    loop {
+        let (batch, has_more) = read_batch(&mut conn, filters, sort_keys, &mut after, batch_size)?;
+        bookmarks.extend(batch);
+        if !has_more {
+            return Ok(bookmarks);
+        }
+    }
+}
@@ -0,0 +1,262 @@
+use std::fs;
+
+use serde::Deserialize;
+use serde_json::Value;
+use time::OffsetDateTime;
+
+use crate::custom_parsers::{Parser, ParserSuccess};
+use crate::models::BookmarkNew;
+use crate::parser_errors::ParserError;
+use crate::structs::{ParseFailBookmark, ParsedBookmarkWithTags, ParserConfig, ScriptOptions};
+use crate::tag_tree::TAG_SEPARATOR;
+
+// Seconds between the WebKit epoch (1601-01-01) and the unix epoch
+const WEBKIT_EPOCH_OFFSET: i64 = 11_644_473_600;
+
+#[derive(Deserialize)]
+struct ChromiumBookmarksFile {
+    roots: ChromiumRoots,
+}
+
+#[derive(Deserialize)]
+struct ChromiumRoots {
+    bookmark_bar: Option<ChromiumFolder>,
+    other: Option<ChromiumFolder>,
+    synced: Option<ChromiumFolder>,
+}
+
+#[derive(Deserialize)]
+struct ChromiumFolder {
+    #[serde(default)]
+    name: String,
+    // Children are read one by one so a broken entry doesn't fail the whole file
+    #[serde(default)]
+    children: Vec<Value>,
+}
+
+#[derive(Deserialize)]
+struct ChromiumUrl {
+    #[serde(default)]
+    name: String,
+    url: String,
+    date_added: Option<String>,
+}
+
+// WebKit timestamps count microseconds since 1601-01-01, stored as a string. 0 means unknown.
+fn webkit_to_unix(date_added: &str) -> Option<i64> {
+    let microseconds: i64 = date_added.trim().parse().ok()?;
+    if microseconds < 0 {
+        return None;
+    }
+    if microseconds == 0 {
+        return Some(OffsetDateTime::now_utc().unix_timestamp());
+    }
+    Some(microseconds / 1_000_000 - WEBKIT_EPOCH_OFFSET)
+}
+
+// Parser for the `Bookmarks` file in the profile directory of Chromium based browsers
+// (Chrome, Edge, Brave, ...). Folder nesting becomes a single nested tag.
+pub struct ChromiumBookmarksParser {
+    name: String,
+    r#type: String,
+    path: String,
+    supported_formats: Vec<String>,
+}
+
+impl ChromiumBookmarksParser {
+    pub fn new() -> Self {
+        ChromiumBookmarksParser {
+            name: "Default Chromium".to_string(),
+            r#type: "default".to_string(),
+            path: "In app".to_string(),
+            supported_formats: vec!["Bookmarks".to_string(), "json".to_string()],
+        }
+    }
+
+    fn parse_content(&self, content: &str) -> Result<ParserSuccess, ParserError> {
+        let file: ChromiumBookmarksFile = serde_json::from_str(content).map_err(|e| {
+            ParserError::InvalidFormat(format!("Not a Chromium bookmarks file: {}", e))
+        })?;
+
+        let mut successful: Vec<ParsedBookmarkWithTags> = Vec::new();
+        let mut failed: Vec<ParseFailBookmark> = Vec::new();
+        let mut index = 0;
+
+        // Use a stack for traversal, children are pushed in reverse to keep their order
+        let mut stack: Vec<(Value, Vec<String>)> = Vec::new();
+        for root in [file.roots.synced, file.roots.other, file.roots.bookmark_bar]
+            .into_iter()
+            .flatten()
+        {
+            let path = folder_path(&[], &root.name);
+            for child in root.children.into_iter().rev() {
+                stack.push((child, path.clone()));
+            }
+        }
+
+        while let Some((node, path)) = stack.pop() {
+            match node.get("type").and_then(|node_type| node_type.as_str()) {
+                Some("folder") => match serde_json::from_value::<ChromiumFolder>(node.clone()) {
+                    Ok(folder) => {
+                        let path = folder_path(&path, &folder.name);
+                        for child in folder.children.into_iter().rev() {
+                            stack.push((child, path.clone()));
+                        }
+                    }
+                    Err(e) => {
+                        failed.push(ParseFailBookmark::new(
+                            index,
+                            node.to_string(),
+                            e.to_string(),
+                        ));
+                        index += 1;
+                    }
+                },
+                Some("url") => {
+                    match serde_json::from_value::<ChromiumUrl>(node.clone()) {
+                        Ok(entry) => match entry.date_added.as_deref().map(webkit_to_unix) {
+                            Some(None) => failed.push(ParseFailBookmark::new(
+                                index,
+                                node.to_string(),
+                                format!(
+                                    "Invalid date_added: {}",
+                                    entry.date_added.unwrap_or_default()
+                                ),
+                            )),
+                            created_at => successful.push(ParsedBookmarkWithTags {
+                                bookmark: BookmarkNew {
+                                    title: Some(entry.name),
+                                    link: entry.url,
+                                    icon_link: None,
+                                    created_at: created_at.flatten().unwrap_or_else(|| {
+                                        OffsetDateTime::now_utc().unix_timestamp()
+                                    }),
+                                },
+                                tags: if path.is_empty() {
+                                    vec![]
+                                } else {
+                                    vec![path.join(&TAG_SEPARATOR.to_string())]
+                                },
+                            }),
+                        },
+                        Err(e) => failed.push(ParseFailBookmark::new(
+                            index,
+                            node.to_string(),
+                            e.to_string(),
+                        )),
+                    }
+                    index += 1;
+                }
+                _ => {
+                    failed.push(ParseFailBookmark::new(
+                        index,
+                        node.to_string(),
+                        "Unknown entry type".to_string(),
+                    ));
+                    index += 1;
+                }
+            }
+        }
+
+        Ok(ParserSuccess::new(successful, failed))
+    }
+}
+
+fn folder_path(parent: &[String], name: &str) -> Vec<String> {
+    let mut path = parent.to_vec();
+    if !name.trim().is_empty() {
+        path.push(name.trim().to_string());
+    }
+    path
+}
+
+impl Parser for ChromiumBookmarksParser {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn parse(&self, input_path: &str) -> Result<ParserSuccess, ParserError> {
+        let content = fs::read_to_string(input_path)
+            .map_err(|e| ParserError::FileReadError(format!("Error reading file: {}", e)))?;
+
+        self.parse_content(&content)
+    }
+
+    fn supported_formats(&self) -> Vec<&str> {
+        self.supported_formats.iter().map(|s| s.as_str()).collect()
+    }
+
+    fn info(&self) -> ParserConfig {
+        ParserConfig {
+            name: self.name.to_string(),
+            r#type: self.r#type.to_string(),
+            path: self.path.to_string(),
+            supported_formats: self.supported_formats.clone(),
+            script: ScriptOptions::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOOKMARKS_JSON: &str = r#"{
+        "checksum": "0",
+        "roots": {
+            "bookmark_bar": {
+                "name": "Bookmarks bar",
+                "type": "folder",
+                "children": [
+                    {"type": "url", "name": "Rust", "url": "https://www.rust-lang.org/", "date_added": "13345000000000000"},
+                    {"type": "folder", "name": "Dev", "children": [
+                        {"type": "url", "name": "Docs", "url": "https://docs.rs/", "date_added": "0"}
+                    ]},
+                    {"type": "url", "name": "No link", "date_added": "13345000000000000"},
+                    {"type": "url", "name": "Bad date", "url": "https://example.com/", "date_added": "-5"}
+                ]
+            },
+            "other": {"name": "Other bookmarks", "type": "folder", "children": []}
+        },
+        "version": 1
+    }"#;
+
+    #[test]
+    fn reads_links_in_order_with_folders_as_tags() {
+        let result = ChromiumBookmarksParser::new()
+            .parse_content(BOOKMARKS_JSON)
+            .unwrap();
+        let successful = result.get_successful();
+        assert_eq!(successful.len(), 2);
+
+        assert_eq!(successful[0].bookmark.link, "https://www.rust-lang.org/");
+        assert_eq!(successful[0].bookmark.title.as_deref(), Some("Rust"));
+        assert_eq!(successful[0].bookmark.created_at, 1700526400);
+        assert_eq!(successful[0].tags, vec!["Bookmarks bar"]);
+
+        assert_eq!(successful[1].bookmark.link, "https://docs.rs/");
+        assert_eq!(successful[1].tags, vec!["Bookmarks bar/Dev"]);
+    }
+
+    #[test]
+    fn reports_broken_entries() {
+        let result = ChromiumBookmarksParser::new()
+            .parse_content(BOOKMARKS_JSON)
+            .unwrap();
+        assert_eq!(result.get_failed().len(), 2);
+    }
+
+    #[test]
+    fn rejects_other_json() {
+        assert!(ChromiumBookmarksParser::new()
+            .parse_content(r#"{"bookmarks": []}"#)
+            .is_err());
+    }
+
+    #[test]
+    fn converts_webkit_timestamps() {
+        assert_eq!(webkit_to_unix("11644473600000000"), Some(0));
+        assert_eq!(webkit_to_unix("-1"), None);
+        assert_eq!(webkit_to_unix("soon"), None);
+    }
+}
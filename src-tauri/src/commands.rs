@@ -1,22 +1,40 @@
-use crate::custom_parsers::{ParserRegistry, PythonParser};
-use crate::database_cmds::batch_insert;
-use crate::models::{Bookmark, Tag};
-use crate::utils::broadcast_info;
+use crate::bookmark_stream::{self, BookmarkStreamBatch, BookmarkStreams, StreamRequest};
+use crate::custom_parsers::{custom_parser, ParserRegistry};
+use crate::database_cmds::batch_insert_with_policies;
+use crate::duplicates::{find_duplicate_groups, DuplicateCriterion, DuplicateGroup};
+use crate::exporters::{ExporterRegistry, PythonExporter};
+use crate::import_batches::ImportSource;
+use crate::import_jobs::{run_import_job, ImportJob, ImportJobs};
+use crate::import_preview::{
+    build_preview, ImportChoice, ImportPreview, ImportPreviews, PendingImport,
+};
+use crate::models::{Bookmark, BookmarkTag, Import, Tag};
+use crate::utils::{broadcast_info, write_app_data_to_storage};
 use diesel::dsl::count;
 use serde::Deserialize;
+use std::path::Path;
 use std::sync::Mutex;
+use tauri::ipc::Channel;
 use tauri::WebviewUrl;
 use tauri::{AppHandle, Manager, State};
 
 use crate::database_cmds;
-use crate::structs::{AppData, ParserConfig};
-use crate::structs::{BookmarkQueryResponse, BookmarkWithTags};
+use crate::pagination::{self, Cursor, SortKey};
+use crate::query_parser::{self, BookmarksQuery, QueryParseError, TagExpression};
+use crate::search;
+use crate::structs::{AppData, DuplicatePolicy, ExporterConfig, InsertCounts, ParserConfig};
+use crate::structs::{BookmarkQueryResponse, BookmarkWithTags, TagWithCount};
+use crate::tag_tree::{build_tree, TagTreeNode};
+
+// Bookmarks read per query when exporting, their tags are loaded in one query per batch
+const EXPORT_BATCH_SIZE: i64 = 500;
 
 #[derive(Debug, Deserialize)]
 #[serde(untagged)]
 pub enum FilterValue {
     Text(String),
     Tags(Vec<String>),
+    TagExpression(TagExpression),
 }
 
 #[derive(Debug, Deserialize)]
@@ -98,127 +116,223 @@ pub fn get_db_path(state: State<'_, Mutex<AppData>>) -> String {
 }
 
 #[tauri::command]
-pub fn create_db(state: State<'_, Mutex<AppData>>, path: String) {
-    let mut app_data = state.lock().unwrap();
-    app_data.db_path = path.clone();
-    app_data.db_pool = database_cmds::establish_connection_pool(&path);
-    let mut conn = app_data.db_pool.get().unwrap();
-    tauri::async_runtime::spawn(async move {
-        database_cmds::run_migrations(&mut conn).await.unwrap();
-    });
+pub fn get_duplicate_policy(state: State<'_, Mutex<AppData>>) -> DuplicatePolicy {
+    let app_data = state.lock().unwrap();
+    app_data.duplicate_policy
 }
 
+// Saved to config.json right away, not only when the app exits cleanly
 #[tauri::command]
-pub fn open_db(state: State<'_, Mutex<AppData>>, path: String) {
+pub fn set_duplicate_policy(app: AppHandle, policy: DuplicatePolicy) {
+    app.state::<Mutex<AppData>>()
+        .lock()
+        .unwrap()
+        .duplicate_policy = policy;
+
+    if let Err(e) = write_app_data_to_storage(&app) {
+        broadcast_info(
+            "Config Error",
+            &format!("Failed to save config: {}", e),
+            log::Level::Error,
+            true,
+        );
+    }
+}
+
+// Migrate the database at `path` and make it the current one. A database that
+// can't be migrated is reported and the current one is kept.
+fn switch_db(app_data: &mut AppData, path: String) -> Result<(), String> {
+    let db_pool = database_cmds::establish_connection_pool(&path);
+    let prepared = db_pool
+        .get()
+        .map_err(|e| format!("Error opening database: {}", e))
+        .and_then(|mut conn| database_cmds::prepare_db(&mut conn, &app_data.tracking_params));
+
+    if let Err(e) = prepared {
+        broadcast_info("Database Error", &e, log::Level::Error, true);
+        return Err(e);
+    }
+
+    app_data.db_path = path;
+    app_data.db_pool = db_pool;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn create_db(state: State<'_, Mutex<AppData>>, path: String) -> Result<(), String> {
     let mut app_data = state.lock().unwrap();
-    app_data.db_path = path.clone();
-    app_data.db_pool = database_cmds::establish_connection_pool(&path);
+    switch_db(&mut app_data, path)
 }
 
 #[tauri::command]
-pub fn get_bookmarks(
-    state: State<'_, Mutex<AppData>>,
-    page: Option<i64>,
-    page_size: Option<i64>,
-    all: Option<bool>,
-    filters: Option<Vec<FilterItem>>,
-    sort: Option<Vec<SortItem>>,
-) -> BookmarkQueryResponse {
+pub fn open_db(state: State<'_, Mutex<AppData>>, path: String) -> Result<(), String> {
+    let mut app_data = state.lock().unwrap();
+    // Bring databases created by older versions up to date
+    switch_db(&mut app_data, path)
+}
+
+// Apply the table filters to a bookmarks query. With `rank` set, full-text filters also
+// order the rows by relevance, until an explicit sort overrides it.
+pub fn apply_filters<'a>(
+    mut query: BookmarksQuery<'a>,
+    filters: &[FilterItem],
+    rank: bool,
+) -> Result<BookmarksQuery<'a>, QueryParseError> {
     use crate::schema::bookmarks_table::dsl::*;
-    use crate::schema::tags_table::{bookmark_id, table as tags_table, tag_name};
     use diesel::prelude::*;
 
-    let app_data = state.lock().unwrap();
-    let mut conn = app_data.db_pool.get().unwrap();
-
-    let mut query = bookmarks_table.into_boxed();
-
-    let mut count_query = bookmarks_table.into_boxed();
-
-    if let Some(filter_items) = &filters {
-        for filter in filter_items {
-            // Apply each filter based on column name
-            match filter.id.as_str() {
-                "title" => {
-                    if let FilterValue::Text(text_value) = &filter.value {
-                        query = query.filter(title.like(format!("%{}%", text_value)));
-                        count_query = count_query.filter(title.like(format!("%{}%", text_value)));
-                    }
+    for filter in filters {
+        // Apply each filter based on column name
+        match filter.id.as_str() {
+            "title" => {
+                if let FilterValue::Text(text_value) = &filter.value {
+                    query = query.filter(title.like(format!("%{}%", text_value)));
                 }
-                "link" => {
-                    if let FilterValue::Text(text_value) = &filter.value {
-                        query = query.filter(link.like(format!("%{}%", text_value)));
-                        count_query = count_query.filter(link.like(format!("%{}%", text_value)));
-                    }
+            }
+            "link" => {
+                if let FilterValue::Text(text_value) = &filter.value {
+                    query = query.filter(link.like(format!("%{}%", text_value)));
                 }
-                "created_at" => {
-                    if let FilterValue::Text(text_value) = &filter.value {
-                        query = query.filter(created_at.eq(text_value.parse::<i64>().unwrap()));
-                        count_query =
-                            count_query.filter(created_at.eq(text_value.parse::<i64>().unwrap()));
+            }
+            "search" => {
+                if let FilterValue::Text(text_value) = &filter.value {
+                    if let Some(match_query) = search::to_match_query(text_value) {
+                        query = query.filter(search::matches(&match_query));
+                        if rank {
+                            query = query.order(search::rank(&match_query));
+                        }
                     }
                 }
-                "tags" => {
-                    if let FilterValue::Tags(tag_values) = &filter.value {
-                        let mut bookmarks_with_tag_ids_query = tags_table.into_boxed();
-                        for (i, tag_value) in tag_values.iter().enumerate() {
-                            if i == 0 {
-                                bookmarks_with_tag_ids_query = bookmarks_with_tag_ids_query
-                                    .filter(tag_name.like(format!("%{}%", tag_value)));
-                            } else {
-                                bookmarks_with_tag_ids_query = bookmarks_with_tag_ids_query
-                                    .or_filter(tag_name.like(format!("%{}%", tag_value)));
-                            }
-                        }
-                        let bookmark_with_tag_ids = bookmarks_with_tag_ids_query
-                            .select(bookmark_id)
-                            .load::<i32>(&mut conn)
-                            .unwrap();
-
-                        query = query.filter(id.eq_any(bookmark_with_tag_ids.clone()));
-                        count_query = count_query.filter(id.eq_any(bookmark_with_tag_ids));
+            }
+            "query" => {
+                if let FilterValue::Text(text_value) = &filter.value {
+                    let parsed_query = query_parser::parse_query(text_value)?;
+                    query = parsed_query.apply_filters(query);
+                    if rank {
+                        query = parsed_query.apply_rank(query);
                     }
                 }
-                // Add more fields as needed
-                _ => {
-                    // Ignore unknown fields
+            }
+            "created_at" => {
+                if let FilterValue::Text(text_value) = &filter.value {
+                    query = query.filter(created_at.eq(text_value.parse::<i64>().unwrap()));
+                }
+            }
+            "tags" => {
+                // A plain list of tags, as the tag picker sends it, matches any of them exactly
+                if let FilterValue::Tags(tag_values) = &filter.value {
+                    let tag_expression = TagExpression {
+                        any_of: tag_values.clone(),
+                        ..TagExpression::default()
+                    };
+                    query = tag_expression.apply_filters(query);
+                } else if let FilterValue::TagExpression(tag_expression) = &filter.value {
+                    query = tag_expression.apply_filters(query);
                 }
             }
+            // Add more fields as needed
+            _ => {
+                // Ignore unknown fields
+            }
         }
     }
 
-    if let Some(sort_items) = &sort {
-        for sort_item in sort_items {
-            match sort_item.id.as_str() {
-                "title" => {
-                    if sort_item.desc {
-                        query = query.order(title.desc());
-                    } else {
-                        query = query.order(title.asc());
-                    }
-                }
-                "link" => {
-                    if sort_item.desc {
-                        query = query.order(link.desc());
-                    } else {
-                        query = query.order(link.asc());
-                    }
+    Ok(query)
+}
+
+// Whether apply_filters with `rank` orders the rows by relevance
+fn is_ranked(filters: &[FilterItem]) -> bool {
+    filters
+        .iter()
+        .any(|filter| match (filter.id.as_str(), &filter.value) {
+            ("search", FilterValue::Text(text_value)) => {
+                search::to_match_query(text_value).is_some()
+            }
+            ("query", FilterValue::Text(text_value)) => query_parser::parse_query(text_value)
+                .map(|parsed_query| parsed_query.is_ranked())
+                .unwrap_or(false),
+            _ => false,
+        })
+}
+
+// Order the rows by the table's sort columns, in the order they were given
+pub fn apply_sort<'a>(
+    mut query: BookmarksQuery<'a>,
+    sort_items: &[SortItem],
+) -> BookmarksQuery<'a> {
+    use crate::schema::bookmarks_table::dsl::*;
+    use diesel::prelude::*;
+
+    for sort_item in sort_items {
+        match sort_item.id.as_str() {
+            "title" => {
+                if sort_item.desc {
+                    query = query.order(title.desc());
+                } else {
+                    query = query.order(title.asc());
                 }
-                "created_at" => {
-                    if sort_item.desc {
-                        query = query.order(created_at.desc());
-                    } else {
-                        query = query.order(created_at.asc());
-                    }
+            }
+            "link" => {
+                if sort_item.desc {
+                    query = query.order(link.desc());
+                } else {
+                    query = query.order(link.asc());
                 }
-                // Add other sortable columns
-                _ => {
-                    // Ignore unknown fields
+            }
+            "created_at" => {
+                if sort_item.desc {
+                    query = query.order(created_at.desc());
+                } else {
+                    query = query.order(created_at.asc());
                 }
             }
+            // Add other sortable columns
+            _ => {
+                // Ignore unknown fields
+            }
         }
     }
 
+    query
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub fn get_bookmarks(
+    state: State<'_, Mutex<AppData>>,
+    page: Option<i64>,
+    page_size: Option<i64>,
+    all: Option<bool>,
+    filters: Option<Vec<FilterItem>>,
+    sort: Option<Vec<SortItem>>,
+    use_cursor: Option<bool>,
+    cursor: Option<String>,
+) -> BookmarkQueryResponse {
+    use crate::schema::bookmarks_table::dsl::*;
+    use diesel::prelude::*;
+
+    let app_data = state.lock().unwrap();
+    let mut conn = app_data.db_pool.get().unwrap();
+
+    let filter_items = filters.as_deref().unwrap_or_default();
+
+    let (mut query, count_query) = match (
+        apply_filters(bookmarks_table.into_boxed(), filter_items, true),
+        apply_filters(bookmarks_table.into_boxed(), filter_items, false),
+    ) {
+        (Ok(query), Ok(count_query)) => (query, count_query),
+        (Err(e), _) | (_, Err(e)) => {
+            return BookmarkQueryResponse {
+                parse_error: Some(e),
+                ..BookmarkQueryResponse::default()
+            };
+        }
+    };
+
+    if let Some(sort_items) = &sort {
+        query = apply_sort(query, sort_items);
+    }
+
     let total = match count_query.select(count(id)).first::<i64>(&mut conn) {
         Ok(total) => total,
         Err(e) => {
@@ -232,6 +346,80 @@ pub fn get_bookmarks(
         }
     };
 
+    // Keyset paging: stable under concurrent inserts and doesn't slow down on deep pages
+    if use_cursor.unwrap_or(false) || cursor.is_some() {
+        let page_size_val = page_size.unwrap_or(10);
+        let total_pages = (total as f64 / page_size_val as f64).ceil() as i64;
+        let sort_keys: Vec<SortKey> = sort
+            .iter()
+            .flatten()
+            .filter_map(|sort_item| SortKey::from_id(&sort_item.id, sort_item.desc))
+            .collect();
+
+        // Relevance isn't a column a cursor can point into, so ranked searches use page numbers
+        if sort_keys.is_empty() && is_ranked(filter_items) {
+            broadcast_info(
+                "Pagination Error",
+                "Cursor paging can't keep search results in relevance order, sort by a column or use page numbers",
+                log::Level::Error,
+                false,
+            );
+            return BookmarkQueryResponse::default();
+        }
+
+        let after = match cursor.as_deref() {
+            Some(encoded) if !encoded.is_empty() => match Cursor::decode(encoded, &sort_keys) {
+                Ok(after) => Some(after),
+                Err(e) => {
+                    broadcast_info(
+                        "Pagination Error",
+                        &format!("Error reading bookmarks: {}", e),
+                        log::Level::Error,
+                        false,
+                    );
+                    return BookmarkQueryResponse::default();
+                }
+            },
+            _ => None,
+        };
+
+        let keyset_query = match pagination::apply_keyset(query, &sort_keys, after.as_ref()) {
+            Ok(keyset_query) => keyset_query,
+            Err(e) => {
+                broadcast_info(
+                    "Pagination Error",
+                    &format!("Error reading bookmarks: {}", e),
+                    log::Level::Error,
+                    false,
+                );
+                return BookmarkQueryResponse::default();
+            }
+        };
+
+        // Fetch one extra row to know whether there is a next page
+        let mut bookmarks = keyset_query
+            .select(Bookmark::as_select())
+            .limit(page_size_val + 1)
+            .load::<Bookmark>(&mut conn)
+            .unwrap();
+        let has_more = bookmarks.len() as i64 > page_size_val;
+        bookmarks.truncate(page_size_val as usize);
+
+        let next_cursor = match bookmarks.last() {
+            Some(last) if has_more => Some(Cursor::after(last, &sort_keys).encode()),
+            _ => None,
+        };
+
+        return BookmarkQueryResponse {
+            bookmarks: attach_tags(&mut conn, bookmarks),
+            total_count: total,
+            total_pages,
+            page: 0,
+            next_cursor,
+            parse_error: None,
+        };
+    }
+
     let mut bookmarks_query = query.select(Bookmark::as_select());
 
     if !all.unwrap_or(false) {
@@ -245,68 +433,139 @@ pub fn get_bookmarks(
 
         let bookmarks = bookmarks_query.load::<Bookmark>(&mut conn).unwrap();
 
-        let tags = Tag::belonging_to(&bookmarks)
-            .select(Tag::as_select())
-            .load(&mut conn)
-            .unwrap();
-
-        let bookmarks_with_tags = tags
-            .grouped_by(&bookmarks)
-            .into_iter()
-            .zip(bookmarks)
-            .map(|(tags, bookmark)| BookmarkWithTags {
-                bookmark,
-                tags: tags.iter().map(|tag| tag.tag_name.clone()).collect(),
-            })
-            .collect::<Vec<BookmarkWithTags>>();
-
         return BookmarkQueryResponse {
-            bookmarks: bookmarks_with_tags,
+            bookmarks: attach_tags(&mut conn, bookmarks),
             total_count: total,
             total_pages,
             page: page_val,
+            next_cursor: None,
+            parse_error: None,
         };
     } else {
         let bookmarks = bookmarks_query.load::<Bookmark>(&mut conn).unwrap();
 
-        let tags = Tag::belonging_to(&bookmarks)
-            .select(Tag::as_select())
-            .load(&mut conn)
-            .unwrap();
-
-        let bookmarks_with_tags = tags
-            .grouped_by(&bookmarks)
-            .into_iter()
-            .zip(bookmarks)
-            .map(|(tags, bookmark)| BookmarkWithTags {
-                bookmark,
-                tags: tags.iter().map(|tag| tag.tag_name.clone()).collect(),
-            })
-            .collect::<Vec<BookmarkWithTags>>();
-
         return BookmarkQueryResponse {
-            bookmarks: bookmarks_with_tags,
+            bookmarks: attach_tags(&mut conn, bookmarks),
             total_count: total,
             total_pages: 1,
             page: 0,
+            next_cursor: None,
+            parse_error: None,
         };
     };
 }
 
+// Load the tags of a page of bookmarks, keeping the page order
+pub fn attach_tags(
+    conn: &mut database_cmds::DbConnection,
+    bookmarks: Vec<Bookmark>,
+) -> Vec<BookmarkWithTags> {
+    use crate::schema::tags;
+    use diesel::prelude::*;
+
+    let bookmark_tags = BookmarkTag::belonging_to(&bookmarks)
+        .inner_join(tags::table)
+        .select((BookmarkTag::as_select(), tags::name))
+        .order(tags::name)
+        .load::<(BookmarkTag, String)>(conn)
+        .unwrap();
+
+    bookmark_tags
+        .grouped_by(&bookmarks)
+        .into_iter()
+        .zip(bookmarks)
+        .map(|(bookmark_tags, bookmark)| BookmarkWithTags {
+            bookmark,
+            tags: bookmark_tags
+                .into_iter()
+                .map(|(_, tag_name)| tag_name)
+                .collect(),
+        })
+        .collect::<Vec<BookmarkWithTags>>()
+}
+
+#[tauri::command]
+pub fn stream_bookmarks(
+    app: AppHandle,
+    filters: Option<Vec<FilterItem>>,
+    sort: Option<Vec<SortItem>>,
+    batch_size: Option<i64>,
+    on_batch: Channel<BookmarkStreamBatch>,
+) -> Result<u32, QueryParseError> {
+    use crate::schema::bookmarks_table::dsl::bookmarks_table;
+    use diesel::prelude::*;
+
+    let filters = filters.unwrap_or_default();
+    // Report malformed queries to the caller before anything is streamed
+    apply_filters(bookmarks_table.into_boxed(), &filters, false)?;
+
+    let sort_keys: Vec<SortKey> = sort
+        .iter()
+        .flatten()
+        .filter_map(|sort_item| SortKey::from_id(&sort_item.id, sort_item.desc))
+        .collect();
+
+    // Only the pool is cloned so the app state isn't locked while streaming
+    let pool = app
+        .state::<Mutex<AppData>>()
+        .lock()
+        .unwrap()
+        .db_pool
+        .clone();
+    let (stream_id, control) = app.state::<Mutex<BookmarkStreams>>().lock().unwrap().open();
+
+    let request = StreamRequest {
+        stream_id,
+        control,
+        filters,
+        sort_keys,
+        batch_size: batch_size
+            .unwrap_or(bookmark_stream::MAX_BATCH_SIZE)
+            .clamp(1, bookmark_stream::MAX_BATCH_SIZE),
+    };
+    let stream_app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        bookmark_stream::run_stream(stream_app, pool, request, on_batch);
+    });
+
+    Ok(stream_id)
+}
+
+#[tauri::command]
+pub fn ack_bookmark_stream(app: AppHandle, stream_id: u32, batch: u64) {
+    let binding = app.state::<Mutex<BookmarkStreams>>();
+    let streams = binding.lock().unwrap();
+    if let Some(control) = streams.get(stream_id) {
+        control.ack(batch);
+    }
+}
+
+#[tauri::command]
+pub fn cancel_bookmark_stream(app: AppHandle, stream_id: u32) {
+    let binding = app.state::<Mutex<BookmarkStreams>>();
+    let streams = binding.lock().unwrap();
+    if let Some(control) = streams.get(stream_id) {
+        control.cancel();
+    }
+}
+
 #[tauri::command]
 pub fn get_all_tags(state: State<'_, Mutex<AppData>>) -> Vec<String> {
-    use crate::schema::tags_table::dsl::*;
+    use crate::schema::{bookmark_tags, tags};
     use diesel::prelude::*;
 
     let app_data = state.lock().unwrap();
     let mut conn = app_data.db_pool.get().unwrap();
 
-    match tags_table
-        .select(tag_name)
+    // Only tags in use, tags left without bookmarks aren't offered
+    match tags::table
+        .inner_join(bookmark_tags::table)
+        .select(tags::name)
         .distinct()
+        .order(tags::name)
         .load::<String>(&mut conn)
     {
-        Ok(tags) => tags,
+        Ok(tag_names) => tag_names,
         Err(e) => {
             broadcast_info(
                 "Database Error",
@@ -320,82 +579,535 @@ pub fn get_all_tags(state: State<'_, Mutex<AppData>>) -> Vec<String> {
 }
 
 #[tauri::command]
-pub fn import_bookmarks(app: AppHandle, file_path: String, parser_name: String) {
+pub fn find_duplicates(
+    state: State<'_, Mutex<AppData>>,
+    criterion: DuplicateCriterion,
+) -> Vec<DuplicateGroup> {
+    // Only the pool is cloned so the app state isn't locked while scanning
+    let pool = state.lock().unwrap().db_pool.clone();
+    let mut conn = database_cmds::get_connection(&pool);
+
+    match find_duplicate_groups(&mut conn, criterion) {
+        Ok(groups) => groups,
+        Err(e) => {
+            broadcast_info(
+                "Database Error",
+                &format!("Error finding duplicates: {}", e),
+                log::Level::Error,
+                true,
+            );
+            vec![]
+        }
+    }
+}
+
+// Every tag with the number of bookmarks carrying it directly, ordered by name
+fn load_tags_with_counts(
+    conn: &mut database_cmds::DbConnection,
+) -> Result<Vec<(Tag, i64)>, diesel::result::Error> {
+    use crate::schema::{bookmark_tags, tags};
+    use diesel::prelude::*;
+
+    tags::table
+        .left_join(bookmark_tags::table)
+        .group_by(tags::id)
+        .select((
+            Tag::as_select(),
+            count(bookmark_tags::bookmark_id.nullable()),
+        ))
+        .order(tags::name)
+        .load::<(Tag, i64)>(conn)
+}
+
+#[tauri::command]
+pub fn get_tags_with_counts(state: State<'_, Mutex<AppData>>) -> Vec<TagWithCount> {
+    let app_data = state.lock().unwrap();
+    let mut conn = app_data.db_pool.get().unwrap();
+
+    match load_tags_with_counts(&mut conn) {
+        Ok(tags_with_counts) => tags_with_counts
+            .into_iter()
+            .map(|(tag, bookmark_count)| TagWithCount {
+                tag,
+                bookmark_count,
+            })
+            .collect(),
+        Err(e) => {
+            broadcast_info(
+                "Database Error",
+                &format!("Error reading tags: {}", e),
+                log::Level::Error,
+                true,
+            );
+            vec![]
+        }
+    }
+}
+
+#[tauri::command]
+pub fn get_tag_tree(state: State<'_, Mutex<AppData>>) -> Vec<TagTreeNode> {
+    let app_data = state.lock().unwrap();
+    let mut conn = app_data.db_pool.get().unwrap();
+
+    match load_tags_with_counts(&mut conn) {
+        Ok(tags_with_counts) => build_tree(tags_with_counts),
+        Err(e) => {
+            broadcast_info(
+                "Database Error",
+                &format!("Error reading tags: {}", e),
+                log::Level::Error,
+                true,
+            );
+            vec![]
+        }
+    }
+}
+
+// Parse and insert a file on a background thread, progress is sent with import-progress
+// events. Returns the id of the job, used to query or cancel it.
+#[tauri::command]
+pub fn import_bookmarks(app: AppHandle, file_path: String, parser_name: String) -> Option<u32> {
+    let parser = match app
+        .state::<Mutex<ParserRegistry>>()
+        .lock()
+        .unwrap()
+        .get(&parser_name)
+    {
+        Some(parser) => parser,
+        None => {
+            broadcast_info(
+                "Bookmarks Import Error",
+                &format!("No parser named {}", parser_name),
+                log::Level::Error,
+                true,
+            );
+            return None;
+        }
+    };
+
+    let control = app
+        .state::<Mutex<ImportJobs>>()
+        .lock()
+        .unwrap()
+        .open(file_path, parser_name);
+    let job_id = control.snapshot().job_id;
+
+    tauri::async_runtime::spawn_blocking(move || run_import_job(app, parser, control));
+
+    Some(job_id)
+}
+
+#[tauri::command]
+pub fn get_import_job(app: AppHandle, job_id: u32) -> Option<ImportJob> {
+    app.state::<Mutex<ImportJobs>>()
+        .lock()
+        .unwrap()
+        .get(job_id)
+        .map(|control| control.snapshot())
+}
+
+#[tauri::command]
+pub fn list_import_jobs(app: AppHandle) -> Vec<ImportJob> {
+    app.state::<Mutex<ImportJobs>>().lock().unwrap().list()
+}
+
+// The job stops after the bookmark it is inserting, everything it inserted is rolled back
+#[tauri::command]
+pub fn cancel_import_job(app: AppHandle, job_id: u32) {
+    if let Some(control) = app.state::<Mutex<ImportJobs>>().lock().unwrap().get(job_id) {
+        control.cancel();
+    }
+}
+
+// First phase of an import: parse the file and sort the entries into new bookmarks,
+// duplicates and failures. Nothing is written until commit_import.
+#[tauri::command]
+pub fn preview_import(
+    app: AppHandle,
+    file_path: String,
+    parser_name: String,
+) -> Option<ImportPreview> {
+    // The registry isn't kept locked while parsing, other imports can start meanwhile
+    let parser = match app
+        .state::<Mutex<ParserRegistry>>()
+        .lock()
+        .unwrap()
+        .get(&parser_name)
+    {
+        Some(parser) => parser,
+        None => {
+            broadcast_info(
+                "Bookmarks Import Error",
+                &format!("No parser named {}", parser_name),
+                log::Level::Error,
+                true,
+            );
+            return None;
+        }
+    };
+
+    let parsed = match parser.parse(&file_path) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            broadcast_info(
+                "Bookmarks Import Error",
+                &format!("Error parsing bookmarks: {}", e),
+                log::Level::Error,
+                true,
+            );
+            return None;
+        }
+    };
+
+    let successful = parsed.get_successful().clone();
+    let preview_id = app
+        .state::<Mutex<ImportPreviews>>()
+        .lock()
+        .unwrap()
+        .open(PendingImport {
+            source: ImportSource {
+                source_file: file_path,
+                parser_name,
+                failed: parsed.get_failed().len(),
+            },
+            parsed: successful.clone(),
+        });
+
+    let app_data_binding = app.state::<Mutex<AppData>>();
+    let app_data = app_data_binding.lock().unwrap();
+    let mut conn = database_cmds::get_connection(&app_data.db_pool);
+
+    match build_preview(
+        &mut conn,
+        preview_id,
+        &successful,
+        parsed.get_failed().clone(),
+        &app_data.tracking_params,
+    ) {
+        Ok(preview) => Some(preview),
+        Err(e) => {
+            app.state::<Mutex<ImportPreviews>>()
+                .lock()
+                .unwrap()
+                .take(preview_id);
+            broadcast_info(
+                "Database Error",
+                &format!("Error reading bookmarks: {}", e),
+                log::Level::Error,
+                true,
+            );
+            None
+        }
+    }
+}
+
+// Second phase of an import: insert the chosen items of a preview. Items without
+// their own policy use `policy`, or the configured duplicate policy when that is None.
+#[tauri::command]
+pub fn commit_import(
+    app: AppHandle,
+    preview_id: u32,
+    choices: Vec<ImportChoice>,
+    policy: Option<DuplicatePolicy>,
+) -> Option<InsertCounts> {
+    let pending = match app
+        .state::<Mutex<ImportPreviews>>()
+        .lock()
+        .unwrap()
+        .take(preview_id)
+    {
+        Some(pending) => pending,
+        None => {
+            broadcast_info(
+                "Bookmarks Import Error",
+                &format!("Import preview {} is no longer available", preview_id),
+                log::Level::Error,
+                true,
+            );
+            return None;
+        }
+    };
+
+    let default_policy = policy.unwrap_or_else(|| {
+        app.state::<Mutex<AppData>>()
+            .lock()
+            .unwrap()
+            .duplicate_policy
+    });
+    let items: Vec<_> = choices
+        .iter()
+        .filter_map(|choice| {
+            pending
+                .parsed
+                .get(choice.index)
+                .map(|item| (item, choice.policy.unwrap_or(default_policy)))
+        })
+        .collect();
+
+    match batch_insert_with_policies(&app, &items, &pending.source) {
+        Ok(counts) => {
+            broadcast_info(
+                "Bookmarks Imported",
+                &format!(
+                    "Successfully imported {} bookmarks ({} duplicates merged, {} updated, {} skipped)",
+                    counts.inserted, counts.merged, counts.updated, counts.skipped
+                ),
+                log::Level::Info,
+                false,
+            );
+            Some(counts)
+        }
+        Err(e) => {
+            broadcast_info(
+                "Bookmarks Import Error",
+                &format!("Error inserting bookmarks: {}", e),
+                log::Level::Error,
+                true,
+            );
+            None
+        }
+    }
+}
+
+#[tauri::command]
+pub fn cancel_import(app: AppHandle, preview_id: u32) {
+    app.state::<Mutex<ImportPreviews>>()
+        .lock()
+        .unwrap()
+        .take(preview_id);
+}
+
+#[tauri::command]
+pub fn get_imports(state: State<'_, Mutex<AppData>>) -> Vec<Import> {
+    use crate::schema::imports::dsl::*;
+    use diesel::prelude::*;
+
+    let app_data = state.lock().unwrap();
+    let mut conn = app_data.db_pool.get().unwrap();
+
+    match imports
+        .order(id.desc())
+        .select(Import::as_select())
+        .load::<Import>(&mut conn)
+    {
+        Ok(import_list) => import_list,
+        Err(e) => {
+            broadcast_info(
+                "Database Error",
+                &format!("Error reading imports: {}", e),
+                log::Level::Error,
+                true,
+            );
+            vec![]
+        }
+    }
+}
+
+#[tauri::command]
+pub fn export_bookmarks(
+    app: AppHandle,
+    file_path: String,
+    exporter_name: String,
+    filters: Option<Vec<FilterItem>>,
+    sort: Option<Vec<SortItem>>,
+) {
+    use crate::schema::bookmarks_table::dsl::bookmarks_table;
+    use diesel::prelude::*;
+
+    // Looked up first, an unknown exporter shouldn't cost reading every bookmark.
+    // It is shared out of the registry, which stays unlocked while it writes
+    let exporter = app
+        .state::<Mutex<ExporterRegistry>>()
+        .lock()
+        .unwrap()
+        .get(&exporter_name);
+    let exporter = match exporter {
+        Some(exporter) => exporter,
+        None => {
+            broadcast_info(
+                "Bookmarks Export Error",
+                &format!("No exporter named {}", exporter_name),
+                log::Level::Error,
+                true,
+            );
+            return;
+        }
+    };
+
+    let filters = filters.unwrap_or_default();
+    if let Err(e) = apply_filters(bookmarks_table.into_boxed(), &filters, false) {
+        broadcast_info(
+            "Bookmarks Export Error",
+            &format!("Invalid filter: {}", e),
+            log::Level::Error,
+            true,
+        );
+        return;
+    }
+
+    let sort_keys: Vec<SortKey> = sort
+        .iter()
+        .flatten()
+        .filter_map(|sort_item| SortKey::from_id(&sort_item.id, sort_item.desc))
+        .collect();
+
+    // Only the pool is cloned so the app state isn't locked while exporting
+    let pool = app
+        .state::<Mutex<AppData>>()
+        .lock()
+        .unwrap()
+        .db_pool
+        .clone();
+
+    let bookmarks = match bookmark_stream::read_all(&pool, &filters, &sort_keys, EXPORT_BATCH_SIZE)
+    {
+        Ok(bookmarks) => bookmarks,
+        Err(e) => {
+            broadcast_info(
+                "Database Error",
+                &format!("Error reading bookmarks: {}", e),
+                log::Level::Error,
+                true,
+            );
+            return;
+        }
+    };
+
+    // Save dialogs don't always add the extension
+    let file_path = if Path::new(&file_path).extension().is_some() {
+        file_path
+    } else {
+        format!("{}.{}", file_path, exporter.format())
+    };
+
+    match exporter.export(&bookmarks, &file_path) {
+        Ok(_) => {
+            broadcast_info(
+                "Bookmarks Exported",
+                &format!(
+                    "Successfully exported {} bookmarks to {}",
+                    bookmarks.len(),
+                    file_path
+                ),
+                log::Level::Info,
+                false,
+            );
+        }
+        Err(e) => {
+            broadcast_info(
+                "Bookmarks Export Error",
+                &format!("Error exporting bookmarks: {}", e),
+                log::Level::Error,
+                true,
+            );
+        }
+    }
+}
+
+#[tauri::command]
+pub fn list_all_custom_parsers(app: AppHandle) -> Vec<ParserConfig> {
     let binding = app.state::<Mutex<ParserRegistry>>();
     let registry = binding.lock().unwrap();
-    let parser = registry.get(&parser_name).unwrap();
-    let parser_result = parser.parse(&file_path);
+    registry
+        .parsers
+        .values()
+        .map(|parser| parser.info())
+        .collect()
+}
+
+#[tauri::command]
+pub fn list_supported_parsers(app: AppHandle, required_format: String) -> Vec<String> {
+    let binding = app.state::<Mutex<ParserRegistry>>();
+    let registry = binding.lock().unwrap();
+    registry.list_parsers_for_format(required_format)
+}
 
-    match parser_result {
-        Ok(parsed_bookmarks) => match batch_insert(&app, parsed_bookmarks.get_successful()) {
+#[tauri::command]
+pub fn add_custom_parser(app: AppHandle, parser_config: ParserConfig) {
+    let registry_binding = app.state::<Mutex<ParserRegistry>>();
+    // Loading the parser may run its interpreter, the registry is only locked to add it
+    match custom_parser(&parser_config) {
+        Ok(parser) => match registry_binding
+            .lock()
+            .unwrap()
+            .register(parser_config.name.clone(), parser)
+        {
             Ok(_) => {
                 broadcast_info(
-                    "Bookmarks Imported",
-                    &format!(
-                        "Successfully imported {} bookmarks",
-                        parsed_bookmarks.get_successful().len()
-                    ),
+                    "Parser Registered",
+                    &format!("Registered parser: {}", parser_config.name),
                     log::Level::Info,
                     false,
                 );
             }
             Err(e) => {
                 broadcast_info(
-                    "Bookmarks Import Error",
-                    &format!("Error inserting bookmarks: {}", e),
+                    "Parser Registration Error",
+                    &format!("Failed to register parser: {}", e),
                     log::Level::Error,
-                    true,
+                    false,
                 );
             }
         },
         Err(e) => {
             broadcast_info(
-                "Bookmarks Import Error",
-                &format!("Error parsing bookmarks: {}", e),
+                "Parser Error",
+                &format!(
+                    "Failed to load {} parser from {}: {}",
+                    parser_config.r#type, parser_config.path, e
+                ),
                 log::Level::Error,
-                true,
+                false,
             );
         }
     }
 }
 
 #[tauri::command]
-pub fn list_all_custom_parsers(app: AppHandle) -> Vec<ParserConfig> {
-    let binding = app.state::<Mutex<ParserRegistry>>();
+pub fn list_exporters(app: AppHandle) -> Vec<ExporterConfig> {
+    let binding = app.state::<Mutex<ExporterRegistry>>();
     let registry = binding.lock().unwrap();
     registry
-        .parsers
+        .exporters
         .values()
-        .map(|parser| parser.info())
+        .map(|exporter| exporter.info())
         .collect()
 }
 
 #[tauri::command]
-pub fn list_supported_parsers(app: AppHandle, required_format: String) -> Vec<String> {
-    let binding = app.state::<Mutex<ParserRegistry>>();
+pub fn list_all_custom_exporters(app: AppHandle) -> Vec<ExporterConfig> {
+    let binding = app.state::<Mutex<ExporterRegistry>>();
     let registry = binding.lock().unwrap();
-    registry.list_parsers_for_format(required_format)
+    registry
+        .exporters
+        .values()
+        .map(|exporter| exporter.info())
+        .filter(|info| info.r#type != "default")
+        .collect()
 }
 
 #[tauri::command]
-pub fn add_custom_parser(app: AppHandle, parser_config: ParserConfig) {
-    let registry_binding = app.state::<Mutex<ParserRegistry>>();
-    let mut registry = registry_binding.lock().unwrap();
-    match parser_config.r#type.as_str() {
-        "python" => match PythonParser::new(&parser_config) {
-            Ok(parser) => match registry.register(parser.name.clone(), Box::new(parser)) {
+pub fn add_custom_exporter(app: AppHandle, exporter_config: ExporterConfig) {
+    let registry_binding = app.state::<Mutex<ExporterRegistry>>();
+    match exporter_config.r#type.as_str() {
+        "python" => match PythonExporter::new(&exporter_config) {
+            Ok(exporter) => match registry_binding
+                .lock()
+                .unwrap()
+                .register(exporter.name.clone(), Box::new(exporter))
+            {
                 Ok(_) => {
                     broadcast_info(
-                        "Parser Registered",
-                        &format!("Registered parser: {}", parser_config.name),
+                        "Exporter Registered",
+                        &format!("Registered exporter: {}", exporter_config.name),
                         log::Level::Info,
                         false,
                     );
                 }
                 Err(e) => {
                     broadcast_info(
-                        "Parser Registration Error",
-                        &format!("Failed to register parser: {}", e),
+                        "Exporter Registration Error",
+                        &format!("Failed to register exporter: {}", e),
                         log::Level::Error,
                         false,
                     );
@@ -403,10 +1115,10 @@ pub fn add_custom_parser(app: AppHandle, parser_config: ParserConfig) {
             },
             Err(e) => {
                 broadcast_info(
-                    "Parser Error",
+                    "Exporter Error",
                     &format!(
-                        "Failed to load Python parser from {}: {}",
-                        parser_config.path, e
+                        "Failed to load Python exporter from {}: {}",
+                        exporter_config.path, e
                     ),
                     log::Level::Error,
                     false,
@@ -415,8 +1127,8 @@ pub fn add_custom_parser(app: AppHandle, parser_config: ParserConfig) {
         },
         _ => {
             broadcast_info(
-                "Parser Error",
-                &format!("Unsupported parser type: {}", parser_config.r#type),
+                "Exporter Error",
+                &format!("Unsupported exporter type: {}", exporter_config.r#type),
                 log::Level::Warn,
                 false,
             );
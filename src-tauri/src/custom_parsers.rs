@@ -1,18 +1,29 @@
 use crate::{
+    embedded_python::parse_in_process,
+    lua_parser::LuaParser,
     models::BookmarkNew,
     parser_errors::ParserError,
-    structs::{BrowserJsonBookmarkItem, ParserConfig},
+    structs::{BrowserJsonBookmarkItem, ParserConfig, ScriptOptions},
+    tag_tree::TAG_SEPARATOR,
+    wasm_parser::WasmParser,
 };
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
+use std::io::{BufRead, BufReader, Read};
 use std::path::Path;
-use std::process::Command;
+use std::process::{Child, ChildStderr, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
-use crate::structs::{ParseFailBookmark, ParsedBookmarkWithTags};
+use crate::structs::{ParseFailBookmark, ParsedBookmarkWithTags, ParserProtocol};
 
+// Parsers are shared so a parse can run without holding the registry's lock
 pub struct ParserRegistry {
-    pub parsers: HashMap<String, Box<dyn Parser>>,
+    pub parsers: HashMap<String, Arc<dyn Parser>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,6 +33,10 @@ pub struct ParserSuccess {
 }
 
 impl ParserSuccess {
+    pub fn new(successful: Vec<ParsedBookmarkWithTags>, failed: Vec<ParseFailBookmark>) -> Self {
+        ParserSuccess { successful, failed }
+    }
+
     pub fn get_successful(&self) -> &Vec<ParsedBookmarkWithTags> {
         &self.successful
     }
@@ -31,6 +46,14 @@ impl ParserSuccess {
     }
 }
 
+// How far a parser got, total is None when the parser can't tell beforehand
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParseProgress {
+    pub processed: usize,
+    #[serde(default)]
+    pub total: Option<usize>,
+}
+
 impl ParserRegistry {
     pub fn new() -> Self {
         Self {
@@ -42,12 +65,12 @@ impl ParserRegistry {
         if self.parsers.contains_key(&name) {
             return Err(format!("Parser with name '{}' already exists", name));
         }
-        self.parsers.insert(name, parser);
+        self.parsers.insert(name, Arc::from(parser));
         Ok(())
     }
 
-    pub fn get(&self, name: &str) -> Option<&dyn Parser> {
-        self.parsers.get(name).map(|p| p.as_ref())
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Parser>> {
+        self.parsers.get(name).cloned()
     }
 
     pub fn list_parsers_for_format(&self, required_format: String) -> Vec<String> {
@@ -74,6 +97,16 @@ impl ParserRegistry {
 pub trait Parser: Send + Sync {
     fn name(&self) -> &str;
     fn parse(&self, input_path: &str) -> Result<ParserSuccess, ParserError>;
+    // Parsers that can report how far they are, or be stopped halfway, override this.
+    // The others just parse.
+    fn parse_with_progress(
+        &self,
+        input_path: &str,
+        _progress: &mut dyn FnMut(&ParseProgress),
+        _cancel: &CancelFlag,
+    ) -> Result<ParserSuccess, ParserError> {
+        self.parse(input_path)
+    }
     fn supported_formats(&self) -> Vec<&str>;
     fn info(&self) -> ParserConfig;
 }
@@ -83,6 +116,43 @@ pub struct PythonParser {
     r#type: String,
     path: String,
     supported_formats: Vec<String>,
+    options: ScriptOptions,
+    // The configured interpreter or the one found on PATH, None when run in process
+    interpreter: Option<String>,
+}
+
+// Whether an interpreter can be started at all. Run from the script's working directory,
+// so a relative interpreter path is found the same way as when the script runs.
+fn interpreter_runs(interpreter: &str, working_dir: Option<&str>) -> bool {
+    let mut command = Command::new(interpreter);
+    if let Some(working_dir) = working_dir {
+        command.current_dir(working_dir);
+    }
+    command
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+// The configured interpreter if it runs. Many distributions only ship python3,
+// so without one python3 and then python are looked up on PATH.
+pub fn find_python_interpreter(
+    interpreter: Option<&str>,
+    working_dir: Option<&str>,
+) -> Result<String, String> {
+    match interpreter {
+        Some(interpreter) if interpreter_runs(interpreter, working_dir) => {
+            Ok(interpreter.to_string())
+        }
+        Some(interpreter) => Err(format!("Python interpreter can't be run: {}", interpreter)),
+        None => ["python3", "python"]
+            .into_iter()
+            .find(|interpreter| interpreter_runs(interpreter, working_dir))
+            .map(|interpreter| interpreter.to_string())
+            .ok_or_else(|| "No python3 or python found on PATH".to_string()),
+    }
 }
 
 impl PythonParser {
@@ -95,15 +165,263 @@ impl PythonParser {
             )));
         }
 
+        let options = &parser_info.script;
+        // The app's own Python is shared by every parse, a script can't get its own
+        // interpreter, arguments, environment or working directory there
+        if options.in_process
+            && (options.interpreter.is_some()
+                || !options.args.is_empty()
+                || !options.env.is_empty()
+                || options.working_dir.is_some())
+        {
+            return Err(ParserError::ConfigError(
+                "interpreter, args, env and workingDir can't be used with inProcess".to_string(),
+            ));
+        }
+        if let Some(working_dir) = &options.working_dir {
+            if !Path::new(working_dir).is_dir() {
+                return Err(ParserError::ConfigError(format!(
+                    "Working directory not found: {}",
+                    working_dir
+                )));
+            }
+        }
+
+        let interpreter = if options.in_process {
+            None
+        } else {
+            Some(
+                find_python_interpreter(
+                    options.interpreter.as_deref(),
+                    options.working_dir.as_deref(),
+                )
+                .map_err(ParserError::ConfigError)?,
+            )
+        };
+
         Ok(Self {
             name: parser_info.name.to_string(),
             r#type: parser_info.r#type.to_string(),
             path: parser_info.path.to_string(),
             supported_formats: parser_info.supported_formats.to_vec(),
+            options: options.clone(),
+            interpreter,
         })
     }
 }
 
+// A line of the jsonl protocol
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum JsonlRecord {
+    Success(ParsedBookmarkWithTags),
+    Failure(ParseFailBookmark),
+    Progress(ParseProgress),
+}
+
+pub fn read_json_output(output: impl Read) -> Result<ParserSuccess, ParserError> {
+    serde_json::from_reader(BufReader::new(output))
+        .map_err(|e| ParserError::InvalidFormat(format!("Failed to parse script output: {}", e)))
+}
+
+// Records are handled as they are printed, so the whole output is never held in memory.
+// A line that isn't a valid record is reported as a failed item instead of ending the parse.
+pub fn read_jsonl_output(
+    output: impl Read,
+    progress: &mut dyn FnMut(&ParseProgress),
+) -> Result<ParserSuccess, ParserError> {
+    let mut successful: Vec<ParsedBookmarkWithTags> = Vec::new();
+    let mut failed: Vec<ParseFailBookmark> = Vec::new();
+
+    for line in BufReader::new(output).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match serde_json::from_str::<JsonlRecord>(&line) {
+            Ok(JsonlRecord::Success(bookmark)) => successful.push(bookmark),
+            Ok(JsonlRecord::Failure(failure)) => failed.push(failure),
+            Ok(JsonlRecord::Progress(update)) => progress(&update),
+            // Numbered like the entries the script reported, blank and progress lines don't count
+            Err(e) => failed.push(ParseFailBookmark::new(
+                (successful.len() + failed.len()) as i32,
+                line,
+                format!("Invalid record: {}", e),
+            )),
+        }
+    }
+
+    Ok(ParserSuccess { successful, failed })
+}
+
+// Stderr goes to the log line by line, prefixed with `source`, and is kept for the error message
+pub fn log_stderr(source: &str, stderr: impl Read) -> String {
+    let mut output = String::new();
+    for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+        log::warn!("{}: {}", source, line);
+        output.push_str(&line);
+        output.push('\n');
+    }
+    output
+}
+
+// Reading stderr while stdin or stdout are in use keeps a chatty process from blocking on a full pipe
+pub fn log_stderr_in_background(source: String, stderr: ChildStderr) -> JoinHandle<String> {
+    thread::spawn(move || log_stderr(&source, stderr))
+}
+
+// Set when a parse isn't wanted anymore, e.g. its import job was cancelled.
+// Clones share the flag, so a watchdog can keep one.
+#[derive(Clone, Default)]
+pub struct CancelFlag(Arc<AtomicBool>);
+
+impl CancelFlag {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// How often a watchdog checks whether its parse was cancelled
+const WATCH_INTERVAL: Duration = Duration::from_millis(100);
+
+// Stops a parser that doesn't finish in time or whose parse was cancelled
+pub struct Watchdog {
+    done: mpsc::Sender<()>,
+    handle: JoinHandle<bool>,
+}
+
+impl Watchdog {
+    pub fn start(
+        timeout: Option<Duration>,
+        cancel: &CancelFlag,
+        on_stop: impl FnOnce() + Send + 'static,
+    ) -> Self {
+        let (done, finished) = mpsc::channel();
+        let cancel = cancel.clone();
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let handle = thread::spawn(move || loop {
+            match finished.recv_timeout(WATCH_INTERVAL) {
+                Err(RecvTimeoutError::Timeout) => {}
+                _ => return false,
+            }
+            if cancel.is_cancelled() || deadline.is_some_and(|deadline| Instant::now() >= deadline)
+            {
+                on_stop();
+                return true;
+            }
+        });
+
+        Watchdog { done, handle }
+    }
+
+    // Returns whether the parser was stopped
+    pub fn stop(self) -> bool {
+        let _ = self.done.send(());
+        self.handle.join().unwrap_or(false)
+    }
+}
+
+// Why a watchdog stopped a parse, to follow the name of what was stopped
+pub fn stop_reason(cancel: &CancelFlag, timeout_secs: Option<u64>) -> String {
+    if cancel.is_cancelled() {
+        "was cancelled".to_string()
+    } else {
+        format!(
+            "timed out after {} seconds",
+            timeout_secs.unwrap_or_default()
+        )
+    }
+}
+
+// How often a process the watchdog may kill is checked for having exited
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+// Wait for a process shared with a watchdog. The lock is only held to check on it,
+// so the watchdog can still kill it in the meantime.
+pub fn wait_for_exit(child: &Mutex<Child>) -> std::io::Result<ExitStatus> {
+    loop {
+        if let Some(status) = child.lock().unwrap().try_wait()? {
+            return Ok(status);
+        }
+        thread::sleep(EXIT_POLL_INTERVAL);
+    }
+}
+
+// What went wrong in a parser process, worded for the kind of parser that ran it
+struct ProcessErrors {
+    label: &'static str,
+    error: fn(String) -> ParserError,
+}
+
+// Run a parser process and read its results from stdout in the configured protocol.
+// Shared by the parsers that run a program, `command` only needs its program and arguments.
+fn run_parser_process(
+    parser_name: &str,
+    mut command: Command,
+    options: &ScriptOptions,
+    errors: ProcessErrors,
+    progress: &mut dyn FnMut(&ParseProgress),
+    cancel: &CancelFlag,
+) -> Result<ParserSuccess, ParserError> {
+    command.envs(&options.env);
+    if let Some(working_dir) = &options.working_dir {
+        command.current_dir(working_dir);
+    }
+
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| (errors.error)(format!("Failed to run {}: {}", errors.label, e)))?;
+
+    // Both are piped, so they are always there
+    let stdout = child.stdout.take().unwrap();
+    let stderr = log_stderr_in_background(
+        format!("Parser {}", parser_name),
+        child.stderr.take().unwrap(),
+    );
+    let child = Arc::new(Mutex::new(child));
+    let watchdog = {
+        let child = child.clone();
+        Watchdog::start(
+            options.timeout_secs.map(Duration::from_secs),
+            cancel,
+            move || {
+                let _ = child.lock().unwrap().kill();
+            },
+        )
+    };
+
+    // Reading ends when the process exits or is killed
+    let result = match options.protocol.unwrap_or_default() {
+        ParserProtocol::Json => read_json_output(stdout),
+        ParserProtocol::Jsonl => read_jsonl_output(stdout, progress),
+    };
+
+    // A script can close stdout and keep running, the watchdog stays on until it exits
+    let status = wait_for_exit(&child)?;
+    let stopped = watchdog.stop();
+    let error = stderr.join().unwrap_or_default();
+
+    if stopped {
+        return Err((errors.error)(format!(
+            "{} {}",
+            errors.label,
+            stop_reason(cancel, options.timeout_secs)
+        )));
+    }
+    if !status.success() {
+        return Err((errors.error)(format!("{} error: {}", errors.label, error)));
+    }
+
+    result
+}
+
 impl Parser for PythonParser {
     fn name(&self) -> &str {
         &self.name
@@ -114,28 +432,219 @@ impl Parser for PythonParser {
     }
 
     fn parse(&self, input_path: &str) -> Result<ParserSuccess, ParserError> {
-        // Run the Python script as a subprocess
-        let output = Command::new("python")
+        self.parse_with_progress(input_path, &mut |_| {}, &CancelFlag::default())
+    }
+
+    fn parse_with_progress(
+        &self,
+        input_path: &str,
+        progress: &mut dyn FnMut(&ParseProgress),
+        cancel: &CancelFlag,
+    ) -> Result<ParserSuccess, ParserError> {
+        let Some(interpreter) = &self.interpreter else {
+            return parse_in_process(
+                &self.path,
+                input_path,
+                self.options.timeout_secs,
+                progress,
+                cancel,
+            );
+        };
+
+        let mut command = Command::new(interpreter);
+        command
             .arg(&self.path)
             .arg(input_path)
-            .output()
-            .map_err(|e| ParserError::PythonError(format!("Failed to run Python script: {}", e)))?;
-
-        if !output.status.success() {
-            let error = String::from_utf8_lossy(&output.stderr);
-            return Err(ParserError::PythonError(format!(
-                "Python script error: {}",
-                error
+            .args(&self.options.args);
+
+        run_parser_process(
+            &self.name,
+            command,
+            &self.options,
+            ProcessErrors {
+                label: "Python script",
+                error: ParserError::PythonError,
+            },
+            progress,
+            cancel,
+        )
+    }
+
+    fn info(&self) -> ParserConfig {
+        ParserConfig {
+            name: self.name.to_string(),
+            r#type: self.r#type.to_string(),
+            path: self.path.to_string(),
+            supported_formats: self.supported_formats.clone(),
+            script: self.options.clone(),
+        }
+    }
+}
+
+// Placeholder in the arguments of a command parser that is replaced by the input path
+const INPUT_PLACEHOLDER: &str = "{input}";
+
+// Runs any executable, a shell or Node script or a compiled binary, with the same
+// output contract as Python parsers
+pub struct CommandParser {
+    pub name: String,
+    r#type: String,
+    path: String,
+    supported_formats: Vec<String>,
+    options: ScriptOptions,
+}
+
+// Whether a file can be run. On Unix it needs an execute bit, on Windows the extension
+// decides and is checked when the candidates are built.
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::metadata(path)
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+// The files a program may be. Windows finds `tool` as `tool.exe`, `tool.cmd` and the other
+// extensions in PATHEXT, a name that already has one of them is only tried as it is.
+fn executable_candidates(path: &Path) -> Vec<std::path::PathBuf> {
+    if !cfg!(windows) {
+        return vec![path.to_path_buf()];
+    }
+
+    let extensions: Vec<String> = std::env::var("PATHEXT")
+        .unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string())
+        .split(';')
+        .filter(|extension| !extension.is_empty())
+        .map(|extension| extension.to_lowercase())
+        .collect();
+    let has_extension = path
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy().to_lowercase()))
+        .is_some_and(|extension| extensions.contains(&extension));
+    if has_extension {
+        return vec![path.to_path_buf()];
+    }
+
+    extensions
+        .iter()
+        .map(|extension| {
+            let mut candidate = path.as_os_str().to_owned();
+            candidate.push(extension);
+            candidate.into()
+        })
+        .collect()
+}
+
+// An executable is either a path or a name found in PATH. Relative paths are resolved
+// against the working directory the executable is started in.
+fn executable_exists(program: &str, working_dir: Option<&str>) -> bool {
+    let path = Path::new(program);
+    if path.components().count() > 1 {
+        let path = match working_dir {
+            Some(working_dir) if path.is_relative() => Path::new(working_dir).join(path),
+            _ => path.to_path_buf(),
+        };
+        return executable_candidates(&path)
+            .iter()
+            .any(|candidate| is_executable(candidate));
+    }
+
+    std::env::var_os("PATH").is_some_and(|paths| {
+        std::env::split_paths(&paths).any(|dir| {
+            executable_candidates(&dir.join(program))
+                .iter()
+                .any(|candidate| is_executable(candidate))
+        })
+    })
+}
+
+impl CommandParser {
+    pub fn new(parser_info: &ParserConfig) -> Result<Self, ParserError> {
+        if !executable_exists(&parser_info.path, parser_info.script.working_dir.as_deref()) {
+            return Err(ParserError::FileReadError(format!(
+                "Executable not found: {}",
+                parser_info.path
             )));
         }
+        if let Some(working_dir) = &parser_info.script.working_dir {
+            if !Path::new(working_dir).is_dir() {
+                return Err(ParserError::ConfigError(format!(
+                    "Working directory not found: {}",
+                    working_dir
+                )));
+            }
+        }
 
-        let json_result = String::from_utf8_lossy(&output.stdout);
+        Ok(Self {
+            name: parser_info.name.to_string(),
+            r#type: parser_info.r#type.to_string(),
+            path: parser_info.path.to_string(),
+            supported_formats: parser_info.supported_formats.to_vec(),
+            options: parser_info.script.clone(),
+        })
+    }
 
-        let parse_result: ParserSuccess = serde_json::from_str(&json_result).map_err(|e| {
-            ParserError::InvalidFormat(format!("Failed to parse script output: {}", e))
-        })?;
+    // {input} in the arguments is replaced by the input path,
+    // without it the input path is the first argument like for Python parsers
+    fn arguments(&self, input_path: &str) -> Vec<String> {
+        if self
+            .options
+            .args
+            .iter()
+            .any(|arg| arg.contains(INPUT_PLACEHOLDER))
+        {
+            return self
+                .options
+                .args
+                .iter()
+                .map(|arg| arg.replace(INPUT_PLACEHOLDER, input_path))
+                .collect();
+        }
+
+        std::iter::once(input_path.to_string())
+            .chain(self.options.args.iter().cloned())
+            .collect()
+    }
+}
+
+impl Parser for CommandParser {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn supported_formats(&self) -> Vec<&str> {
+        self.supported_formats.iter().map(|s| s.as_str()).collect()
+    }
+
+    fn parse(&self, input_path: &str) -> Result<ParserSuccess, ParserError> {
+        self.parse_with_progress(input_path, &mut |_| {}, &CancelFlag::default())
+    }
 
-        Ok(parse_result)
+    fn parse_with_progress(
+        &self,
+        input_path: &str,
+        progress: &mut dyn FnMut(&ParseProgress),
+        cancel: &CancelFlag,
+    ) -> Result<ParserSuccess, ParserError> {
+        let mut command = Command::new(&self.path);
+        command.args(self.arguments(input_path));
+
+        run_parser_process(
+            &self.name,
+            command,
+            &self.options,
+            ProcessErrors {
+                label: "Command",
+                error: ParserError::CommandError,
+            },
+            progress,
+            cancel,
+        )
     }
 
     fn info(&self) -> ParserConfig {
@@ -144,10 +653,25 @@ impl Parser for PythonParser {
             r#type: self.r#type.to_string(),
             path: self.path.to_string(),
             supported_formats: self.supported_formats.clone(),
+            script: self.options.clone(),
         }
     }
 }
 
+// Build a custom parser from its config entry, the type picks the implementation
+pub fn custom_parser(parser_info: &ParserConfig) -> Result<Box<dyn Parser>, ParserError> {
+    match parser_info.r#type.as_str() {
+        "python" => Ok(Box::new(PythonParser::new(parser_info)?)),
+        "command" => Ok(Box::new(CommandParser::new(parser_info)?)),
+        "lua" => Ok(Box::new(LuaParser::new(parser_info)?)),
+        "wasm" => Ok(Box::new(WasmParser::new(parser_info)?)),
+        other => Err(ParserError::ConfigError(format!(
+            "Unsupported parser type: {}",
+            other
+        ))),
+    }
+}
+
 // JSON Parser implementation
 pub struct BrowserJsonParser {
     name: String,
@@ -173,23 +697,14 @@ impl Parser for BrowserJsonParser {
     }
 
     fn parse(&self, input_path: &str) -> Result<ParserSuccess, ParserError> {
-        // Read the file
-        let file_content = fs::read_to_string(&input_path);
+        // Read the file, errors are reported by the import like those of every other parser
+        let content = fs::read_to_string(input_path)
+            .map_err(|e| ParserError::FileReadError(format!("Error reading file: {}", e)))?;
 
         // Parse JSON
-        let root = match file_content {
-            Ok(content) => {
-                let bookmarks: BrowserJsonBookmarkItem = serde_json::from_str(&content).unwrap();
-                bookmarks
-            }
-            Err(e) => {
-                eprintln!("Error reading file: {}", e);
-                return Err(ParserError::FileReadError(format!(
-                    "Error reading file: {}",
-                    e
-                )));
-            }
-        };
+        let root: BrowserJsonBookmarkItem = serde_json::from_str(&content).map_err(|e| {
+            ParserError::InvalidFormat(format!("Not a browser JSON bookmarks file: {}", e))
+        })?;
 
         // Convert to ParseResult
 
@@ -210,14 +725,18 @@ impl Parser for BrowserJsonParser {
                             icon_link: Some(bookmark_entry.icon_uri.clone().unwrap_or_default()),
                             created_at: bookmark_entry.base.date_added / 1000,
                         },
-                        tags: current_tags.clone(),
+                        tags: if current_tags.is_empty() {
+                            vec![]
+                        } else {
+                            vec![current_tags.join(&TAG_SEPARATOR.to_string())]
+                        },
                     };
 
                     // Add the parsed bookmark to our collection
                     successful.push(bookmark);
                 }
                 BrowserJsonBookmarkItem::Folder(folder) => {
-                    // Create new tags list with folder title added
+                    // Extend the folder path, the bookmark gets a single nested tag for it
                     let mut new_tags = current_tags.clone();
                     if !folder.base.title.trim().is_empty() {
                         new_tags.push(folder.base.title.trim().to_string());
@@ -244,6 +763,7 @@ impl Parser for BrowserJsonParser {
             r#type: self.r#type.to_string(),
             path: self.path.to_string(),
             supported_formats: self.supported_formats.clone(),
+            script: ScriptOptions::default(),
         }
     }
 }
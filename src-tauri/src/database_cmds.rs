@@ -1,7 +1,13 @@
-use crate::models::{BookmarkNew, TagNew};
+use crate::duplicates::merge_bookmarks;
+use crate::import_batches::{
+    finish_import, rollback_import, snapshot_bookmark, start_import, ImportSource, RollbackError,
+};
+use crate::models::{BookmarkNew, BookmarkTag, Tag, TagMetadata, TagNew};
+use crate::schema::bookmark_tags::bookmark_id;
 use crate::schema::bookmarks_table::id;
-use crate::schema::tags_table::bookmark_id;
-use crate::structs::ParsedBookmarkWithTags;
+use crate::structs::{DuplicatePolicy, InsertCounts, InsertOutcome, ParsedBookmarkWithTags};
+use crate::tag_tree::{is_descendant, normalize_tag_path, parent_path, subtree_ids, TAG_SEPARATOR};
+use crate::url_canonical::canonicalize_url;
 use crate::utils::broadcast_info;
 use diesel::backend::Backend;
 use diesel::prelude::*;
@@ -21,21 +27,25 @@ pub type DbConnection = PooledConnection<ConnectionManager<SqliteConnection>>;
 
 // Create a custom connection customizer
 #[derive(Debug)]
-struct SqliteForeignKeyCustomizer;
+struct SqliteConnectionCustomizer;
 
-impl<C, E> CustomizeConnection<C, E> for SqliteForeignKeyCustomizer
+impl<C, E> CustomizeConnection<C, E> for SqliteConnectionCustomizer
 where
     C: diesel::connection::SimpleConnection,
     E: Error + Send + Sync + 'static + std::convert::From<diesel::r2d2::Error>,
 {
     fn on_acquire(&self, conn: &mut C) -> Result<(), E> {
-        conn.batch_execute("PRAGMA foreign_keys = ON")
-            .map_err(|e| diesel::r2d2::Error::QueryError(e))?;
+        // Background imports write while the UI reads. WAL lets readers carry on during a
+        // write, and the busy timeout makes a second writer wait instead of failing with SQLITE_BUSY.
+        conn.batch_execute(
+            "PRAGMA foreign_keys = ON; PRAGMA busy_timeout = 5000; PRAGMA journal_mode = WAL;",
+        )
+        .map_err(|e| diesel::r2d2::Error::QueryError(e))?;
         Ok(())
     }
 }
 
-pub async fn run_migrations<DB: Backend>(
+pub fn run_migrations<DB: Backend>(
     connection: &mut impl MigrationHarness<DB>,
 ) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
     // This will run the necessary migrations.
@@ -44,6 +54,43 @@ pub async fn run_migrations<DB: Backend>(
     Ok(())
 }
 
+// Migrate the database, then fill in the columns that can't be computed by the
+// migrations themselves. Runs before the database is used, so no query sees an old schema.
+pub fn prepare_db(conn: &mut DbConnection, tracking_params: &[String]) -> Result<(), String> {
+    run_migrations(conn).map_err(|e| format!("Error migrating database: {}", e))?;
+
+    backfill_canonical_links(conn, tracking_params)
+        .map_err(|e| format!("Error computing canonical links: {}", e))?;
+
+    Ok(())
+}
+
+// Compute the canonical link of every bookmark that doesn't have one yet
+pub fn backfill_canonical_links(
+    conn: &mut SqliteConnection,
+    tracking_params: &[String],
+) -> QueryResult<usize> {
+    use crate::schema::bookmarks_table;
+
+    let missing: Vec<(i32, String)> = bookmarks_table::table
+        .filter(bookmarks_table::canonical_link.eq(""))
+        .select((bookmarks_table::id, bookmarks_table::link))
+        .load(conn)?;
+
+    conn.transaction(|conn| {
+        for (missing_id, missing_link) in &missing {
+            diesel::update(bookmarks_table::table.find(missing_id))
+                .set(
+                    bookmarks_table::canonical_link
+                        .eq(canonicalize_url(missing_link, tracking_params)),
+                )
+                .execute(conn)?;
+        }
+
+        Ok(missing.len())
+    })
+}
+
 // Initialize the connection pool
 pub fn establish_connection_pool(path: &str) -> DbPool {
     let database_url = format!("sqlite://{}", path);
@@ -51,7 +98,7 @@ pub fn establish_connection_pool(path: &str) -> DbPool {
     let manager = ConnectionManager::<SqliteConnection>::new(database_url);
     Pool::builder()
         .max_size(5)
-        .connection_customizer(Box::new(SqliteForeignKeyCustomizer))
+        .connection_customizer(Box::new(SqliteConnectionCustomizer))
         .build(manager)
         .expect("Failed to create connection pool")
 }
@@ -61,55 +108,322 @@ pub fn get_connection(pool: &DbPool) -> DbConnection {
     pool.get().expect("Failed to get connection from pool")
 }
 
-pub fn batch_insert(
-    app: &AppHandle,
-    bookmarks: &Vec<ParsedBookmarkWithTags>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // Get database connection from app state
-    let binding = app.state::<Mutex<AppData>>();
-    let app_data = binding.lock().unwrap();
-    let mut conn = get_connection(&app_data.db_pool);
+// Look up the ids of the given tag names, creating the tags that don't exist yet.
+// Nested names ("dev/rust") also create their missing ancestors. Blank names are skipped.
+pub fn get_or_create_tag_ids(
+    conn: &mut SqliteConnection,
+    tag_names: &[String],
+) -> QueryResult<Vec<i32>> {
+    tag_names
+        .iter()
+        .filter_map(|tag_name| normalize_tag_path(tag_name))
+        .map(|tag_path| get_or_create_tag_path(conn, &tag_path))
+        .collect()
+}
+
+// Id of the tag at a normalized path, creating it and every missing level above it
+fn get_or_create_tag_path(conn: &mut SqliteConnection, tag_path: &str) -> QueryResult<i32> {
+    use crate::schema::tags;
+
+    let mut parent_id: Option<i32> = None;
+    let mut current_path = String::new();
+
+    for level in tag_path.split(TAG_SEPARATOR) {
+        if !current_path.is_empty() {
+            current_path.push(TAG_SEPARATOR);
+        }
+        current_path.push_str(level);
+
+        // Insert tag with ON CONFLICT DO NOTHING behavior, names are unique
+        diesel::insert_into(tags::table)
+            .values(&TagNew {
+                name: current_path.clone(),
+                parent_id,
+            })
+            .on_conflict_do_nothing()
+            .execute(conn)?;
+
+        parent_id = Some(
+            tags::table
+                .filter(tags::name.eq(&current_path))
+                .select(tags::id)
+                .first(conn)?,
+        );
+    }
+
+    parent_id.ok_or(diesel::result::Error::NotFound)
+}
+
+// Tag a bookmark. Pairs added by an import carry its id so they can be rolled back,
+// pairs that already exist are left alone by imports. Tagging by hand claims an existing
+// pair back from its import, so rolling the import back keeps the tag.
+pub fn add_bookmark_tags(
+    conn: &mut SqliteConnection,
+    tagged_bookmark_id: i32,
+    tag_names: &[String],
+    import: Option<i32>,
+) -> QueryResult<()> {
+    use crate::schema::bookmark_tags;
+
+    for tag_id in get_or_create_tag_ids(conn, tag_names)? {
+        let bookmark_tag = BookmarkTag {
+            bookmark_id: tagged_bookmark_id,
+            tag_id,
+        };
+
+        let insert = diesel::insert_into(bookmark_tags::table)
+            .values((&bookmark_tag, bookmark_tags::import_id.eq(import)));
+        match import {
+            Some(_) => insert.on_conflict_do_nothing().execute(conn)?,
+            None => insert
+                .on_conflict((bookmark_tags::bookmark_id, bookmark_tags::tag_id))
+                .do_update()
+                .set(bookmark_tags::import_id.eq(None::<i32>))
+                .execute(conn)?,
+        };
+    }
+
+    Ok(())
+}
+
+// Give the tag `old_name` and everything nested under it the path `new_name`,
+// "dev" -> "code" turns "dev/rust" into "code/rust". A tag whose new path is
+// already taken is merged into the existing one.
+pub fn move_tag(conn: &mut SqliteConnection, old_name: &str, new_name: &str) -> QueryResult<()> {
+    use crate::schema::{bookmark_tags, tags};
+
+    let mut subtree: Vec<Tag> = tags::table
+        .filter(tags::id.eq_any(subtree_ids(&[old_name.to_string()])))
+        .select(Tag::as_select())
+        .load(conn)?;
+
+    // Parents first, so each one is renamed in place and keeps its metadata,
+    // its children then find it at the new path
+    subtree.sort_by_key(|tag| tag.name.len());
+
+    for tag in subtree {
+        let moved_name = format!("{}{}", new_name, &tag.name[old_name.len()..]);
+        let parent_id = match parent_path(&moved_name) {
+            Some(parent) => Some(get_or_create_tag_path(conn, parent)?),
+            None => None,
+        };
+        let existing = tags::table
+            .filter(tags::name.eq(&moved_name))
+            .select(tags::id)
+            .first::<i32>(conn)
+            .optional()?;
+
+        match existing {
+            Some(existing_id) if existing_id != tag.id => {
+                // Pairs the existing tag already has are ignored thanks to the primary key
+                diesel::insert_or_ignore_into(bookmark_tags::table)
+                    .values(
+                        bookmark_tags::table
+                            .filter(bookmark_tags::tag_id.eq(tag.id))
+                            .select((
+                                bookmark_tags::bookmark_id,
+                                existing_id.into_sql::<diesel::sql_types::Integer>(),
+                            )),
+                    )
+                    .into_columns((bookmark_tags::bookmark_id, bookmark_tags::tag_id))
+                    .execute(conn)?;
+
+                // Removing the tag cascades to its bookmark_tags rows
+                diesel::delete(tags::table.find(tag.id)).execute(conn)?;
+            }
+            _ => {
+                diesel::update(tags::table.find(tag.id))
+                    .set((tags::name.eq(&moved_name), tags::parent_id.eq(parent_id)))
+                    .execute(conn)?;
+            }
+        }
+    }
+
+    Ok(())
+}
 
-    let bookmarks_temp: Vec<(BookmarkNew, Vec<String>)> = bookmarks
+// Move every bookmark tagged with one of `source_names` over to `target_name`,
+// then drop the source tags. Tags nested under a source move under the target.
+// Bookmarks that already have the target tag keep a single copy.
+pub fn merge_tags(
+    conn: &mut SqliteConnection,
+    source_names: &[String],
+    target_name: &str,
+) -> QueryResult<()> {
+    let target_name = normalize_tag_path(target_name).ok_or(diesel::result::Error::NotFound)?;
+    get_or_create_tag_path(conn, &target_name)?;
+
+    for source_name in source_names
         .iter()
-        .cloned()
-        .map(|bookmark_with_tags| (bookmark_with_tags.bookmark, bookmark_with_tags.tags))
-        .collect();
+        .filter_map(|source_name| normalize_tag_path(source_name))
+    {
+        // A tag can't be merged into itself or into one of its own descendants
+        if source_name == target_name || is_descendant(&target_name, &source_name) {
+            continue;
+        }
+        move_tag(conn, &source_name, &target_name)?;
+    }
 
-    // Begin transaction
-    conn.transaction(|conn| {
-        use crate::schema::{bookmarks_table, tags_table};
-        use diesel::prelude::*;
+    Ok(())
+}
 
-        for (bookmark, tags) in bookmarks_temp {
+// Insert a bookmark with its tags. When a bookmark with the same canonical link
+// is already stored, the duplicate policy decides what happens to that one instead.
+// `import` is the import the bookmark is part of, if any.
+pub fn insert_bookmark(
+    conn: &mut SqliteConnection,
+    bookmark: &BookmarkNew,
+    tags: &[String],
+    policy: DuplicatePolicy,
+    tracking_params: &[String],
+    import: Option<i32>,
+) -> QueryResult<InsertOutcome> {
+    use crate::schema::{bookmark_tags, bookmarks_table};
+
+    let canonical_link = canonicalize_url(&bookmark.link, tracking_params);
+    let existing = bookmarks_table::table
+        .filter(bookmarks_table::canonical_link.eq(&canonical_link))
+        .select(bookmarks_table::id)
+        .order(bookmarks_table::id)
+        .first::<i32>(conn)
+        .optional()?;
+
+    match (existing, policy) {
+        (None, _) => {
             // Insert bookmark and get ID
-            let curr_bookmark_id: i32 = diesel::insert_into(bookmarks_table::table)
-                .values(&bookmark)
-                .returning(bookmarks_table::id) // Assuming id is the column name
+            let insert_id: i32 = diesel::insert_into(bookmarks_table::table)
+                .values((
+                    bookmark,
+                    bookmarks_table::canonical_link.eq(&canonical_link),
+                    bookmarks_table::import_id.eq(import),
+                ))
+                .returning(bookmarks_table::id)
                 .get_result(conn)?;
 
-            // Insert tags for this specific bookmark
-            for tag_name in tags {
-                // Create a new tag record
-                let tag = TagNew {
-                    bookmark_id: curr_bookmark_id,
-                    tag_name,
-                };
-
-                // Insert tag with ON CONFLICT DO NOTHING behavior
-                diesel::insert_into(tags_table::table)
-                    .values(&tag)
-                    .on_conflict_do_nothing()
-                    .execute(conn)?;
+            // Insert tags for this specific bookmark, empty tags are skipped
+            add_bookmark_tags(conn, insert_id, tags, import)?;
+            Ok(InsertOutcome::Inserted)
+        }
+        (Some(_), DuplicatePolicy::Skip) => Ok(InsertOutcome::Skipped),
+        (Some(existing_id), DuplicatePolicy::MergeTags) => {
+            add_bookmark_tags(conn, existing_id, tags, import)?;
+            Ok(InsertOutcome::MergedTags)
+        }
+        (Some(existing_id), DuplicatePolicy::Update) => {
+            if let Some(import) = import {
+                snapshot_bookmark(conn, import, existing_id)?;
             }
+
+            diesel::update(bookmarks_table::table.find(existing_id))
+                .set((
+                    bookmark,
+                    bookmarks_table::canonical_link.eq(&canonical_link),
+                ))
+                .execute(conn)?;
+
+            diesel::delete(bookmark_tags::table)
+                .filter(bookmark_id.eq(existing_id))
+                .execute(conn)?;
+            add_bookmark_tags(conn, existing_id, tags, import)?;
+            Ok(InsertOutcome::Updated)
         }
+    }
+}
 
-        Ok(()) as Result<(), diesel::result::Error>
-    })?;
+// Bookmarks inserted per transaction by an import. Other writes, from the UI or a deep link,
+// only wait for the chunk being written instead of the whole import.
+const IMPORT_CHUNK_SIZE: usize = 200;
+
+// Insert the bookmarks of an import, each with its own duplicate policy, and record
+// the import so it can be rolled back later. `progress` is called after every bookmark,
+// returning false cancels the import. The bookmarks are committed in chunks under the
+// import's id, a cancelled or failed import is rolled back so nothing of it is kept.
+pub fn insert_import(
+    conn: &mut SqliteConnection,
+    bookmarks: &[(&ParsedBookmarkWithTags, DuplicatePolicy)],
+    source: &ImportSource,
+    tracking_params: &[String],
+    mut progress: impl FnMut(&InsertCounts) -> bool,
+) -> QueryResult<InsertCounts> {
+    let import = start_import(conn, source)?;
+    let mut counts = InsertCounts::default();
+
+    for chunk in bookmarks.chunks(IMPORT_CHUNK_SIZE) {
+        // Duplicates within the import are caught as well, earlier chunks are committed
+        let result = conn.transaction(|conn| {
+            for (bookmark_with_tags, policy) in chunk {
+                counts.add(insert_bookmark(
+                    conn,
+                    &bookmark_with_tags.bookmark,
+                    &bookmark_with_tags.tags,
+                    *policy,
+                    tracking_params,
+                    Some(import),
+                )?);
+
+                if !progress(&counts) {
+                    return Err(diesel::result::Error::RollbackTransaction);
+                }
+            }
+            Ok(())
+        });
+
+        if let Err(e) = result {
+            undo_partial_import(conn, import, &counts)?;
+            return Err(e);
+        }
+    }
+
+    finish_import(conn, import, &counts)?;
+
+    Ok(counts)
+}
+
+// Roll back the chunks a cancelled or failed import already committed
+fn undo_partial_import(
+    conn: &mut SqliteConnection,
+    import: i32,
+    counts: &InsertCounts,
+) -> QueryResult<()> {
+    match conn.transaction(|conn| rollback_import(conn, import)) {
+        Ok(_) => Ok(()),
+        Err(RollbackError::Database(e)) => Err(e),
+        // An import that ran alongside changed the same bookmarks, undoing this one
+        // would lose its changes. What was committed is kept and can be rolled back later.
+        Err(e @ RollbackError::OverwrittenLater(_)) => {
+            finish_import(conn, import, counts)?;
+            broadcast_info(
+                "Import Partially Kept",
+                &format!(
+                    "The bookmarks imported before the import stopped were kept, {}",
+                    e
+                ),
+                log::Level::Warn,
+                true,
+            );
+            Ok(())
+        }
+    }
+}
+
+pub fn batch_insert_with_policies(
+    app: &AppHandle,
+    bookmarks: &[(&ParsedBookmarkWithTags, DuplicatePolicy)],
+    source: &ImportSource,
+) -> Result<InsertCounts, Box<dyn std::error::Error>> {
+    // Only the pool is cloned so the app state isn't locked while inserting
+    let (pool, tracking_params) = {
+        let binding = app.state::<Mutex<AppData>>();
+        let app_data = binding.lock().unwrap();
+        (app_data.db_pool.clone(), app_data.tracking_params.clone())
+    };
+    let mut conn = get_connection(&pool);
+
+    let counts = insert_import(&mut conn, bookmarks, source, &tracking_params, |_| true)?;
 
     app.emit("bookmarks-updated", {}).unwrap();
 
-    Ok(())
+    Ok(counts)
 }
 
 #[tauri::command]
@@ -121,36 +435,16 @@ pub fn bookmark_insert(app: AppHandle, bookmark: BookmarkNew, tags: Vec<String>)
 
     // Begin transaction
     match conn.transaction(|conn| {
-        use crate::schema::{bookmarks_table, tags_table};
-        use diesel::prelude::*;
-
-        // Insert bookmark and get ID
-        let insert_id: i32 = diesel::insert_into(bookmarks_table::table)
-            .values(&bookmark)
-            .returning(bookmarks_table::id) // Assuming id is the column name
-            .get_result(conn)?;
-
-        // Insert tags for this specific bookmark
-        for tag_name in tags {
-            if tag_name.is_empty() {
-                continue; // Skip empty tags
-            }
-            // Create a new tag record
-            let tag = TagNew {
-                bookmark_id: insert_id,
-                tag_name,
-            };
-
-            // Insert tag with ON CONFLICT DO NOTHING behavior
-            diesel::insert_into(tags_table::table)
-                .values(&tag)
-                .on_conflict_do_nothing()
-                .execute(conn)?;
-        }
-
-        Ok(()) as Result<(), diesel::result::Error>
+        insert_bookmark(
+            conn,
+            &bookmark,
+            &tags,
+            app_data.duplicate_policy,
+            &app_data.tracking_params,
+            None,
+        )
     }) {
-        Ok(_) => {
+        Ok(InsertOutcome::Inserted) => {
             app.emit("bookmarks-updated", "bookmarks-updated").unwrap();
             broadcast_info(
                 "Bookmark Inserted",
@@ -159,6 +453,23 @@ pub fn bookmark_insert(app: AppHandle, bookmark: BookmarkNew, tags: Vec<String>)
                 false,
             );
         }
+        Ok(InsertOutcome::Skipped) => {
+            broadcast_info(
+                "Duplicate Bookmark",
+                "A bookmark with this link already exists",
+                log::Level::Info,
+                false,
+            );
+        }
+        Ok(InsertOutcome::MergedTags) | Ok(InsertOutcome::Updated) => {
+            app.emit("bookmarks-updated", "bookmarks-updated").unwrap();
+            broadcast_info(
+                "Duplicate Bookmark",
+                "A bookmark with this link already exists, it was updated instead",
+                log::Level::Info,
+                false,
+            );
+        }
         Err(e) => {
             broadcast_info(
                 "Database Error",
@@ -179,35 +490,26 @@ pub fn bookmark_update(app: AppHandle, index: i32, bookmark: BookmarkNew, tags:
 
     // Begin transaction
     match conn.transaction(|conn| {
-        use crate::schema::{bookmarks_table, tags_table};
+        use crate::schema::{bookmark_tags, bookmarks_table};
         use diesel::prelude::*;
 
         // Update bookmark
         diesel::update(bookmarks_table::table)
             .filter(id.eq(index))
-            .set(&bookmark)
+            .set((
+                &bookmark,
+                bookmarks_table::canonical_link
+                    .eq(canonicalize_url(&bookmark.link, &app_data.tracking_params)),
+            ))
             .execute(conn)?;
 
         // Delete existing tags for this bookmark
-        diesel::delete(tags_table::table)
+        diesel::delete(bookmark_tags::table)
             .filter(bookmark_id.eq(index))
             .execute(conn)?;
 
         // Insert new tags for this specific bookmark
-        for tag_name in tags {
-            // Create a new tag record
-            let tag = TagNew {
-                bookmark_id: index,
-                tag_name,
-            };
-
-            // Insert tag with ON CONFLICT DO NOTHING behavior
-            diesel::insert_into(tags_table::table)
-                .values(&tag)
-                .on_conflict_do_nothing()
-                .execute(conn)
-                .unwrap();
-        }
+        add_bookmark_tags(conn, index, &tags, None)?;
         Ok(()) as Result<(), diesel::result::Error>
     }) {
         Ok(_) => {
@@ -279,34 +581,34 @@ pub fn tags_update(
     let app_data = binding.lock().unwrap();
     let mut conn = get_connection(&app_data.db_pool);
 
+    // Names are matched the way add_bookmark_tags stores them
+    let tags_to_delete: Vec<String> = tags_to_delete
+        .iter()
+        .filter_map(|tag_name| normalize_tag_path(tag_name))
+        .collect();
+
     // Begin transaction
     match conn.transaction(|conn| {
-        use crate::schema::tags_table;
+        use crate::schema::{bookmark_tags, tags};
         use diesel::prelude::*;
 
         // Delete specified tags
-        for tag_name in tags_to_delete {
-            diesel::delete(
-                tags_table::table
-                    .filter(tags_table::tag_name.eq(tag_name))
-                    .filter(tags_table::bookmark_id.eq_any(&ids)),
-            )
-            .execute(conn)?;
-        }
+        diesel::delete(
+            bookmark_tags::table
+                .filter(
+                    bookmark_tags::tag_id.eq_any(
+                        tags::table
+                            .filter(tags::name.eq_any(&tags_to_delete))
+                            .select(tags::id),
+                    ),
+                )
+                .filter(bookmark_tags::bookmark_id.eq_any(&ids)),
+        )
+        .execute(conn)?;
 
         // Insert new tags for the specified bookmarks
         for tag_update_id in ids {
-            for tag_name in &tags_to_add {
-                let tag = TagNew {
-                    bookmark_id: tag_update_id,
-                    tag_name: tag_name.clone(),
-                };
-
-                diesel::insert_into(tags_table::table)
-                    .values(&tag)
-                    .on_conflict_do_nothing()
-                    .execute(conn)?;
-            }
+            add_bookmark_tags(conn, tag_update_id, &tags_to_add, None)?;
         }
 
         Ok(()) as Result<(), diesel::result::Error>
@@ -331,6 +633,285 @@ pub fn tags_update(
     }
 }
 
+#[tauri::command]
+pub fn tag_update_metadata(app: AppHandle, tag_name: String, metadata: TagMetadata) {
+    // Get database connection from app state
+    let binding = app.state::<Mutex<AppData>>();
+    let app_data = binding.lock().unwrap();
+    let mut conn = get_connection(&app_data.db_pool);
+
+    use crate::schema::tags;
+
+    match diesel::update(tags::table)
+        .filter(tags::name.eq(&tag_name))
+        .set(&metadata)
+        .execute(&mut conn)
+    {
+        Ok(0) => {
+            broadcast_info(
+                "Tag Not Found",
+                &format!("No tag named {}", tag_name),
+                log::Level::Warn,
+                false,
+            );
+        }
+        Ok(_) => {
+            app.emit("tags-updated", "tags-updated").unwrap();
+            broadcast_info(
+                "Tag Updated",
+                "Tag updated successfully",
+                log::Level::Info,
+                false,
+            );
+        }
+        Err(e) => {
+            broadcast_info(
+                "Database Error",
+                &format!("Error updating tag: {}", e),
+                log::Level::Error,
+                true,
+            );
+        }
+    }
+}
+
+#[tauri::command]
+pub fn tag_rename(app: AppHandle, old_name: String, new_name: String) {
+    // Get database connection from app state
+    let binding = app.state::<Mutex<AppData>>();
+    let app_data = binding.lock().unwrap();
+    let mut conn = get_connection(&app_data.db_pool);
+
+    let old_name = normalize_tag_path(&old_name).unwrap_or_default();
+    let new_name = match normalize_tag_path(&new_name) {
+        Some(new_name) => new_name,
+        None => {
+            broadcast_info(
+                "Invalid Tag Name",
+                "Tag name cannot be empty",
+                log::Level::Warn,
+                false,
+            );
+            return;
+        }
+    };
+    if is_descendant(&new_name, &old_name) {
+        broadcast_info(
+            "Invalid Tag Name",
+            "A tag cannot be moved inside itself",
+            log::Level::Warn,
+            false,
+        );
+        return;
+    }
+
+    // Begin transaction, renaming onto a tag that already exists merges the two
+    match conn.transaction(|conn| {
+        use crate::schema::tags;
+
+        tags::table
+            .filter(tags::name.eq(&old_name))
+            .select(tags::id)
+            .first::<i32>(conn)?;
+        move_tag(conn, &old_name, &new_name)
+    }) {
+        Ok(_) => {
+            app.emit("tags-updated", "tags-updated").unwrap();
+            app.emit("bookmarks-updated", "bookmarks-updated").unwrap();
+            broadcast_info(
+                "Tag Renamed",
+                &format!("Renamed {} to {}", old_name, new_name),
+                log::Level::Info,
+                false,
+            );
+        }
+        Err(diesel::result::Error::NotFound) => {
+            broadcast_info(
+                "Tag Not Found",
+                &format!("No tag named {}", old_name),
+                log::Level::Warn,
+                false,
+            );
+        }
+        Err(e) => {
+            broadcast_info(
+                "Database Error",
+                &format!("Error renaming tag: {}", e),
+                log::Level::Error,
+                true,
+            );
+        }
+    }
+}
+
+#[tauri::command]
+pub fn tags_merge(app: AppHandle, source_names: Vec<String>, target_name: String) {
+    // Get database connection from app state
+    let binding = app.state::<Mutex<AppData>>();
+    let app_data = binding.lock().unwrap();
+    let mut conn = get_connection(&app_data.db_pool);
+
+    let target_name = target_name.trim().to_string();
+    if target_name.is_empty() {
+        broadcast_info(
+            "Invalid Tag Name",
+            "Tag name cannot be empty",
+            log::Level::Warn,
+            false,
+        );
+        return;
+    }
+
+    // Begin transaction
+    match conn.transaction(|conn| merge_tags(conn, &source_names, &target_name)) {
+        Ok(_) => {
+            app.emit("tags-updated", "tags-updated").unwrap();
+            app.emit("bookmarks-updated", "bookmarks-updated").unwrap();
+            broadcast_info(
+                "Tags Merged",
+                &format!("Merged {} tags into {}", source_names.len(), target_name),
+                log::Level::Info,
+                false,
+            );
+        }
+        Err(e) => {
+            broadcast_info(
+                "Database Error",
+                &format!("Error merging tags: {}", e),
+                log::Level::Error,
+                true,
+            );
+        }
+    }
+}
+
+#[tauri::command]
+pub fn tag_delete(app: AppHandle, tag_name: String) {
+    // Get database connection from app state
+    let binding = app.state::<Mutex<AppData>>();
+    let app_data = binding.lock().unwrap();
+    let mut conn = get_connection(&app_data.db_pool);
+
+    use crate::schema::tags;
+
+    let tag_name = normalize_tag_path(&tag_name).unwrap_or_default();
+
+    // Nested tags go with their parent, and removing a tag cascades to every bookmark that has it
+    match diesel::delete(
+        tags::table.filter(tags::id.eq_any(subtree_ids(std::slice::from_ref(&tag_name)))),
+    )
+    .execute(&mut conn)
+    {
+        Ok(0) => {
+            broadcast_info(
+                "Tag Not Found",
+                &format!("No tag named {}", tag_name),
+                log::Level::Warn,
+                false,
+            );
+        }
+        Ok(_) => {
+            app.emit("tags-updated", "tags-updated").unwrap();
+            app.emit("bookmarks-updated", "bookmarks-updated").unwrap();
+            broadcast_info(
+                "Tag Deleted",
+                &format!("Removed {} from all bookmarks", tag_name),
+                log::Level::Info,
+                false,
+            );
+        }
+        Err(e) => {
+            broadcast_info(
+                "Database Error",
+                &format!("Error deleting tag: {}", e),
+                log::Level::Error,
+                true,
+            );
+        }
+    }
+}
+
+#[tauri::command]
+pub fn merge_duplicates(app: AppHandle, ids: Vec<i32>) {
+    // Get database connection from app state
+    let binding = app.state::<Mutex<AppData>>();
+    let app_data = binding.lock().unwrap();
+    let mut conn = get_connection(&app_data.db_pool);
+
+    if ids.len() < 2 {
+        broadcast_info(
+            "Nothing To Merge",
+            "Select at least two bookmarks to merge",
+            log::Level::Warn,
+            false,
+        );
+        return;
+    }
+
+    // Begin transaction
+    match conn.transaction(|conn| merge_bookmarks(conn, &ids)) {
+        Ok(_) => {
+            app.emit("bookmarks-updated", "bookmarks-updated").unwrap();
+            broadcast_info(
+                "Bookmarks Merged",
+                &format!("Merged {} bookmarks into one", ids.len()),
+                log::Level::Info,
+                false,
+            );
+        }
+        Err(e) => {
+            broadcast_info(
+                "Database Error",
+                &format!("Error merging bookmarks: {}", e),
+                log::Level::Error,
+                true,
+            );
+        }
+    }
+}
+
+// Undo a past import, see import_batches::rollback_import
+#[tauri::command]
+pub fn import_rollback(app: AppHandle, import_id: i32) {
+    // Get database connection from app state
+    let binding = app.state::<Mutex<AppData>>();
+    let app_data = binding.lock().unwrap();
+    let mut conn = get_connection(&app_data.db_pool);
+
+    // Begin transaction, the import is undone entirely or not at all
+    match conn.transaction(|conn| rollback_import(conn, import_id)) {
+        Ok(counts) => {
+            app.emit("bookmarks-updated", "bookmarks-updated").unwrap();
+            app.emit("tags-updated", "tags-updated").unwrap();
+            broadcast_info(
+                "Import Rolled Back",
+                &format!(
+                    "Removed {} bookmarks, restored {} and removed {} added tags, {} tags were left unused and deleted",
+                    counts.removed, counts.restored, counts.untagged, counts.deleted_tags
+                ),
+                log::Level::Info,
+                false,
+            );
+        }
+        Err(e @ RollbackError::OverwrittenLater(_)) => {
+            broadcast_info(
+                "Rollback Refused",
+                &format!("Import {} can't be rolled back yet: {}", import_id, e),
+                log::Level::Warn,
+                true,
+            );
+        }
+        Err(e) => {
+            broadcast_info(
+                "Database Error",
+                &format!("Error rolling back import: {}", e),
+                log::Level::Error,
+                true,
+            );
+        }
+    }
+}
+
 #[tauri::command]
 pub fn batch_delete(app: AppHandle, ids: Vec<i32>) {
     // Get database connection from app state
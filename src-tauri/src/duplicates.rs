@@ -0,0 +1,233 @@
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::sql_types::{Bool, Integer, Text};
+use serde::{Deserialize, Serialize};
+
+use crate::commands::attach_tags;
+use crate::database_cmds::DbConnection;
+use crate::models::Bookmark;
+use crate::structs::BookmarkWithTags;
+
+// Bookmarks whose tags are loaded in one query, stays below SQLite's variable limit
+const TAGS_CHUNK_SIZE: usize = 500;
+
+// How two bookmarks are recognised as the same
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateCriterion {
+    // Identical link, character for character
+    ExactLink,
+    // Identical canonical link, see url_canonical
+    CanonicalLink,
+    // Same host, path and query string, whatever the scheme or #fragment
+    HostAndPath,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateGroup {
+    pub key: String,
+    pub bookmarks: Vec<BookmarkWithTags>,
+}
+
+// SQL expression of the key bookmarks are grouped by. Canonical links have the form
+// `scheme://host/path?query#fragment`, links that aren't URLs are kept as they are.
+fn duplicate_key(criterion: DuplicateCriterion) -> String {
+    match criterion {
+        DuplicateCriterion::ExactLink => "link".to_string(),
+        DuplicateCriterion::CanonicalLink => "canonical_link".to_string(),
+        DuplicateCriterion::HostAndPath => {
+            let without_scheme = "CASE WHEN instr(canonical_link, '://') > 0 \
+                THEN substr(canonical_link, instr(canonical_link, '://') + 3) \
+                ELSE canonical_link END";
+            // The query stays, `watch?v=` links are different videos
+            format!("substr({0}, 1, instr({0} || '#', '#') - 1)", without_scheme)
+        }
+    }
+}
+
+// Bookmarks sharing a key, grouped by that key. SQLite does the grouping, groups are
+// sorted by key and bookmarks within a group by creation. Bookmarks without a duplicate are left out.
+pub fn find_duplicate_groups(
+    conn: &mut DbConnection,
+    criterion: DuplicateCriterion,
+) -> QueryResult<Vec<DuplicateGroup>> {
+    use crate::schema::bookmarks_table::dsl::*;
+
+    let key = duplicate_key(criterion);
+    let rows: Vec<(String, Bookmark)> = bookmarks_table
+        .filter(sql::<Bool>(&format!(
+            "{0} <> '' AND {0} IN (SELECT {0} FROM bookmarks_table GROUP BY 1 HAVING COUNT(*) > 1)",
+            key
+        )))
+        .select((sql::<Text>(&key), Bookmark::as_select()))
+        .order((sql::<Text>(&key), created_at, id))
+        .load(conn)?;
+
+    let mut groups: Vec<DuplicateGroup> = Vec::new();
+    for chunk in rows.chunks(TAGS_CHUNK_SIZE) {
+        let bookmarks = attach_tags(
+            conn,
+            chunk.iter().map(|(_, bookmark)| bookmark.clone()).collect(),
+        );
+        for ((group_key, _), bookmark) in chunk.iter().zip(bookmarks) {
+            match groups.last_mut() {
+                Some(group) if &group.key == group_key => group.bookmarks.push(bookmark),
+                _ => groups.push(DuplicateGroup {
+                    key: group_key.clone(),
+                    bookmarks: vec![bookmark],
+                }),
+            }
+        }
+    }
+
+    Ok(groups)
+}
+
+// The most descriptive title of a group: the longest one that isn't empty or just the link
+fn best_title(bookmarks: &[Bookmark]) -> Option<String> {
+    bookmarks
+        .iter()
+        .filter_map(|bookmark| {
+            let title = bookmark.title.as_deref()?.trim();
+            if title.is_empty() || title == bookmark.link {
+                None
+            } else {
+                Some(title)
+            }
+        })
+        .max_by_key(|title| title.chars().count())
+        .or_else(|| {
+            bookmarks
+                .iter()
+                .filter_map(|bookmark| bookmark.title.as_deref())
+                .find(|title| !title.trim().is_empty())
+        })
+        .map(|title| title.to_string())
+}
+
+// Fold a group of bookmarks into one, must be run inside a transaction.
+// The earliest bookmark is kept, it gets the best title of the group, an icon if it
+// lacks one and the union of all tags. The others are deleted. Returns the kept id.
+pub fn merge_bookmarks(conn: &mut SqliteConnection, ids: &[i32]) -> QueryResult<i32> {
+    use crate::schema::{bookmark_tags, bookmarks_table};
+
+    let group: Vec<Bookmark> = bookmarks_table::table
+        .filter(bookmarks_table::id.eq_any(ids))
+        .order((bookmarks_table::created_at, bookmarks_table::id))
+        .select(Bookmark::as_select())
+        .load(conn)?;
+
+    let kept = match group.first() {
+        Some(kept) => kept.clone(),
+        None => return Err(diesel::result::Error::NotFound),
+    };
+    let merged_ids: Vec<i32> = group[1..].iter().map(|bookmark| bookmark.id).collect();
+
+    let icon_link = kept
+        .icon_link
+        .clone()
+        .filter(|icon| !icon.is_empty())
+        .or_else(|| {
+            group
+                .iter()
+                .filter_map(|bookmark| bookmark.icon_link.clone())
+                .find(|icon| !icon.is_empty())
+        });
+
+    diesel::update(bookmarks_table::table.find(kept.id))
+        .set((
+            bookmarks_table::title.eq(best_title(&group)),
+            bookmarks_table::icon_link.eq(icon_link),
+        ))
+        .execute(conn)?;
+
+    // Pairs the kept bookmark already has are ignored thanks to the primary key
+    diesel::insert_or_ignore_into(bookmark_tags::table)
+        .values(
+            bookmark_tags::table
+                .filter(bookmark_tags::bookmark_id.eq_any(&merged_ids))
+                .select((kept.id.into_sql::<Integer>(), bookmark_tags::tag_id)),
+        )
+        .into_columns((bookmark_tags::bookmark_id, bookmark_tags::tag_id))
+        .execute(conn)?;
+
+    // Deleting the others cascades to their bookmark_tags rows
+    diesel::delete(bookmarks_table::table.filter(bookmarks_table::id.eq_any(&merged_ids)))
+        .execute(conn)?;
+
+    Ok(kept.id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::Connection;
+
+    // The key SQLite computes for a bookmark
+    fn key_of(criterion: DuplicateCriterion, link: &str, canonical_link: &str) -> String {
+        let mut conn = SqliteConnection::establish(":memory:").unwrap();
+        diesel::select(sql::<Text>(&format!(
+            "(SELECT {} FROM (SELECT '{}' AS link, '{}' AS canonical_link))",
+            duplicate_key(criterion),
+            link,
+            canonical_link
+        )))
+        .get_result(&mut conn)
+        .unwrap()
+    }
+
+    #[test]
+    fn host_and_path_ignores_scheme_and_fragment() {
+        let criterion = DuplicateCriterion::HostAndPath;
+        assert_eq!(
+            key_of(criterion, "", "https://example.com/a#top"),
+            "example.com/a"
+        );
+        assert_eq!(
+            key_of(criterion, "", "http://example.com/a"),
+            "example.com/a"
+        );
+    }
+
+    #[test]
+    fn host_and_path_keeps_the_query() {
+        let criterion = DuplicateCriterion::HostAndPath;
+        assert_eq!(
+            key_of(criterion, "", "https://youtube.com/watch?v=1#t=5"),
+            "youtube.com/watch?v=1"
+        );
+        assert_ne!(
+            key_of(criterion, "", "https://youtube.com/watch?v=1"),
+            key_of(criterion, "", "https://youtube.com/watch?v=2")
+        );
+    }
+
+    #[test]
+    fn links_that_are_not_urls_are_their_own_key() {
+        assert_eq!(
+            key_of(DuplicateCriterion::HostAndPath, "", "notes about rust"),
+            "notes about rust"
+        );
+    }
+
+    #[test]
+    fn exact_and_canonical_use_their_column() {
+        assert_eq!(
+            key_of(
+                DuplicateCriterion::ExactLink,
+                "HTTP://A.com",
+                "http://a.com/"
+            ),
+            "HTTP://A.com"
+        );
+        assert_eq!(
+            key_of(
+                DuplicateCriterion::CanonicalLink,
+                "HTTP://A.com",
+                "http://a.com/"
+            ),
+            "http://a.com/"
+        );
+    }
+}
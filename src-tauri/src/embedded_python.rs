@@ -0,0 +1,225 @@
+use std::ffi::CString;
+use std::fs;
+use std::os::raw::c_long;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use time::OffsetDateTime;
+
+use crate::custom_parsers::{stop_reason, CancelFlag, ParseProgress, ParserSuccess, Watchdog};
+use crate::models::BookmarkNew;
+use crate::parser_errors::ParserError;
+use crate::structs::{ParseFailBookmark, ParsedBookmarkWithTags};
+
+// Progress is reported after this many items of the returned bookmarks
+const PROGRESS_INTERVAL: usize = 100;
+
+// Numbers the imported modules, so parsers with scripts of the same name don't share one
+static MODULE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+// Value of a key that may be missing or None
+fn optional<'py, T: FromPyObject<'py>>(
+    dict: &Bound<'py, PyDict>,
+    key: &str,
+) -> PyResult<Option<T>> {
+    match dict.get_item(key)? {
+        Some(value) if !value.is_none() => value.extract().map(Some),
+        _ => Ok(None),
+    }
+}
+
+// A dict with the keys of the JSON custom parsers print, only link is required
+impl<'py> FromPyObject<'py> for ParsedBookmarkWithTags {
+    fn extract_bound(item: &Bound<'py, PyAny>) -> PyResult<Self> {
+        let dict = item.downcast::<PyDict>()?;
+        let link: String =
+            optional(dict, "link")?.ok_or_else(|| pyo3::exceptions::PyKeyError::new_err("link"))?;
+
+        Ok(ParsedBookmarkWithTags {
+            bookmark: BookmarkNew {
+                title: optional(dict, "title")?,
+                link,
+                icon_link: optional(dict, "icon_link")?,
+                created_at: optional(dict, "created_at")?
+                    .unwrap_or_else(|| OffsetDateTime::now_utc().unix_timestamp()),
+            },
+            tags: optional(dict, "tags")?.unwrap_or_default(),
+        })
+    }
+}
+
+// The exception with its traceback, like Python prints it
+fn python_error(py: Python<'_>, err: PyErr) -> ParserError {
+    let traceback = err
+        .traceback(py)
+        .and_then(|traceback| traceback.format().ok())
+        .unwrap_or_default();
+    ParserError::PythonError(format!("{}{}", traceback, err))
+}
+
+// Raise KeyboardInterrupt in the thread running a parse, it stops at the next line of Python.
+// Not an Exception, so scripts catching those don't swallow it. Passing no exception
+// clears one that wasn't raised yet.
+fn interrupt_thread(thread_id: c_long, interrupt: bool) {
+    Python::with_gil(|_| unsafe {
+        let exception = if interrupt {
+            pyo3::ffi::PyExc_KeyboardInterrupt
+        } else {
+            std::ptr::null_mut()
+        };
+        pyo3::ffi::PyThreadState_SetAsyncExc(thread_id, exception);
+    });
+}
+
+// Import the script as a module and call its parse(path). It can return the
+// {"successful": [...], "failed": [...]} dict of the JSON protocol, or any iterable of
+// bookmark dicts, a generator included. Items that don't convert are reported as failed.
+pub fn parse_in_process(
+    script_path: &str,
+    input_path: &str,
+    timeout_secs: Option<u64>,
+    progress: &mut dyn FnMut(&ParseProgress),
+    cancel: &CancelFlag,
+) -> Result<ParserSuccess, ParserError> {
+    let source = fs::read_to_string(script_path).map_err(|e| {
+        ParserError::FileReadError(format!("Python script not found: {}: {}", script_path, e))
+    })?;
+    let script = Path::new(script_path);
+    let module_name = format!(
+        "{}_{}",
+        script
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| "parser".to_string()),
+        MODULE_COUNT.fetch_add(1, Ordering::Relaxed)
+    );
+    let to_c_string = |text: &str| {
+        CString::new(text)
+            .map_err(|e| ParserError::InvalidFormat(format!("Invalid Python script: {}", e)))
+    };
+    let (source, file_name, module_name) = (
+        to_c_string(&source)?,
+        to_c_string(script_path)?,
+        to_c_string(&module_name)?,
+    );
+
+    // Python's id of this thread, the watchdog interrupts the parse through it
+    let thread_id = Python::with_gil(|py| {
+        py.import("threading")?
+            .getattr("get_ident")?
+            .call0()?
+            .extract::<u64>()
+    })? as c_long;
+    let watchdog = Watchdog::start(timeout_secs.map(Duration::from_secs), cancel, move || {
+        interrupt_thread(thread_id, true)
+    });
+
+    let result = Python::with_gil(|py| {
+        let sys = py.import("sys")?;
+        let sys_path = sys
+            .getattr("path")?
+            .downcast_into::<PyList>()
+            .map_err(PyErr::from)?;
+        // A copy to put back afterwards, so one parser's directory isn't searched by the next
+        let original_sys_path = sys_path.get_slice(0, sys_path.len());
+        // Modules loaded before the parse, the script and the helpers it imports are dropped
+        // afterwards so the next parser doesn't get a module of the same name from this one
+        let original_modules = sys
+            .getattr("modules")?
+            .downcast_into::<PyDict>()
+            .map_err(PyErr::from)?
+            .copy()?;
+
+        let mut run = || -> PyResult<(Vec<ParsedBookmarkWithTags>, Vec<ParseFailBookmark>)> {
+            // Modules next to the script can be imported, like when it is run directly
+            if let Some(directory) = script.parent().and_then(|directory| directory.to_str()) {
+                if !sys_path.contains(directory)? {
+                    sys_path.insert(0, directory)?;
+                }
+            }
+
+            let module = PyModule::from_code(py, &source, &file_name, &module_name)?;
+            let result = module.getattr("parse")?.call1((input_path,))?;
+
+            let mut failed: Vec<ParseFailBookmark> = Vec::new();
+            let items = match result.downcast::<PyDict>() {
+                Ok(dict) => {
+                    for failure in dict
+                        .get_item("failed")?
+                        .map(|failures| failures.try_iter())
+                        .transpose()?
+                        .into_iter()
+                        .flatten()
+                    {
+                        let failure = failure?;
+                        let failure = failure.downcast::<PyDict>()?;
+                        failed.push(ParseFailBookmark::new(
+                            optional(failure, "index")?.unwrap_or_default(),
+                            optional(failure, "item")?.unwrap_or_default(),
+                            optional(failure, "error")?.unwrap_or_default(),
+                        ));
+                    }
+                    match dict.get_item("successful")? {
+                        Some(successful) => successful,
+                        None => PyList::empty(py).into_any(),
+                    }
+                }
+                Err(_) => result,
+            };
+
+            // Generators have no length
+            let total = items.len().ok();
+            let mut successful: Vec<ParsedBookmarkWithTags> = Vec::new();
+            for (index, item) in items.try_iter()?.enumerate() {
+                let item = item?;
+                match item.extract::<ParsedBookmarkWithTags>() {
+                    Ok(bookmark) => successful.push(bookmark),
+                    Err(e) => failed.push(ParseFailBookmark::new(
+                        index as i32,
+                        item.repr().map(|repr| repr.to_string()).unwrap_or_default(),
+                        format!("Invalid bookmark: {}", e),
+                    )),
+                }
+
+                if (index + 1) % PROGRESS_INTERVAL == 0 {
+                    progress(&ParseProgress {
+                        processed: index + 1,
+                        total,
+                    });
+                }
+            }
+
+            Ok((successful, failed))
+        };
+
+        let result = run();
+        sys_path.set_slice(0, sys_path.len(), &original_sys_path)?;
+        let modules = sys
+            .getattr("modules")?
+            .downcast_into::<PyDict>()
+            .map_err(PyErr::from)?;
+        for name in modules.keys() {
+            if !original_modules.contains(&name)? {
+                modules.del_item(name)?;
+            }
+        }
+        let (successful, failed) = result.map_err(|e| python_error(py, e))?;
+        Ok(ParserSuccess::new(successful, failed))
+    });
+
+    // The GIL is released, so a watchdog waiting for it to interrupt can finish.
+    // An interrupt that came too late must not hit what this thread runs next.
+    let stopped = watchdog.stop();
+    if stopped {
+        interrupt_thread(thread_id, false);
+        return Err(ParserError::PythonError(format!(
+            "Python script {}",
+            stop_reason(cancel, timeout_secs)
+        )));
+    }
+
+    result
+}
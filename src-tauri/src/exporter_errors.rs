@@ -0,0 +1,32 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum ExporterError {
+    FileWriteError(String),
+    Serialization(String),
+    ConfigError(String),
+    PythonError(String),
+    IoError(io::Error),
+}
+
+impl fmt::Display for ExporterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExporterError::FileWriteError(msg) => write!(f, "File write error: {}", msg),
+            ExporterError::Serialization(msg) => write!(f, "Serialization error: {}", msg),
+            ExporterError::ConfigError(msg) => write!(f, "Configuration error: {}", msg),
+            ExporterError::PythonError(msg) => write!(f, "Python error: {}", msg),
+            ExporterError::IoError(e) => write!(f, "IO error: {}", e),
+        }
+    }
+}
+
+impl Error for ExporterError {}
+
+impl From<io::Error> for ExporterError {
+    fn from(err: io::Error) -> Self {
+        ExporterError::IoError(err)
+    }
+}
@@ -0,0 +1,437 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::custom_parsers::{
+    find_python_interpreter, log_stderr_in_background, wait_for_exit, CancelFlag, ParserSuccess,
+    Watchdog,
+};
+use crate::exporter_errors::ExporterError;
+use crate::models::BookmarkNew;
+use crate::structs::{BookmarkWithTags, ExporterConfig, ExporterOptions, ParsedBookmarkWithTags};
+use crate::tag_tree::TAG_SEPARATOR;
+
+// Exporters are shared so an export can run without holding the registry's lock
+pub struct ExporterRegistry {
+    pub exporters: HashMap<String, Arc<dyn Exporter>>,
+}
+
+impl ExporterRegistry {
+    pub fn new() -> Self {
+        Self {
+            exporters: HashMap::new(),
+        }
+    }
+
+    pub fn register(&mut self, name: String, exporter: Box<dyn Exporter>) -> Result<(), String> {
+        if self.exporters.contains_key(&name) {
+            return Err(format!("Exporter with name '{}' already exists", name));
+        }
+        self.exporters.insert(name, Arc::from(exporter));
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Exporter>> {
+        self.exporters.get(name).cloned()
+    }
+
+    pub fn remove_exporters(&mut self) {
+        self.exporters.clear();
+    }
+}
+
+pub trait Exporter: Send + Sync {
+    fn name(&self) -> &str;
+    fn export(
+        &self,
+        bookmarks: &[BookmarkWithTags],
+        output_path: &str,
+    ) -> Result<(), ExporterError>;
+    fn format(&self) -> &str;
+    fn info(&self) -> ExporterConfig;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    // bookmarks.html that browsers import, tags become folders
+    NetscapeHtml,
+    // The JSON custom parsers print, so an export can be read back by one
+    PcPocketJson,
+    Csv,
+    Markdown,
+}
+
+pub struct PythonExporter {
+    pub name: String,
+    r#type: String,
+    path: String,
+    format: String,
+    options: ExporterOptions,
+    // The configured interpreter or the one found on PATH
+    interpreter: String,
+}
+
+impl PythonExporter {
+    pub fn new(exporter_info: &ExporterConfig) -> Result<Self, ExporterError> {
+        // Validate that the script exists
+        if !Path::new(&exporter_info.path).exists() {
+            return Err(ExporterError::ConfigError(format!(
+                "Python script not found: {}",
+                exporter_info.path
+            )));
+        }
+
+        let options = &exporter_info.script;
+        if let Some(working_dir) = &options.working_dir {
+            if !Path::new(working_dir).is_dir() {
+                return Err(ExporterError::ConfigError(format!(
+                    "Working directory not found: {}",
+                    working_dir
+                )));
+            }
+        }
+        let interpreter = find_python_interpreter(
+            options.interpreter.as_deref(),
+            options.working_dir.as_deref(),
+        )
+        .map_err(ExporterError::ConfigError)?;
+
+        Ok(Self {
+            name: exporter_info.name.to_string(),
+            r#type: exporter_info.r#type.to_string(),
+            path: exporter_info.path.to_string(),
+            format: exporter_info.format.to_string(),
+            options: options.clone(),
+            interpreter,
+        })
+    }
+}
+
+impl Exporter for PythonExporter {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    // The script gets the output path as its argument and the bookmarks on stdin,
+    // in the same JSON the Default JSON exporter writes
+    fn export(
+        &self,
+        bookmarks: &[BookmarkWithTags],
+        output_path: &str,
+    ) -> Result<(), ExporterError> {
+        let input = render_pcpocket_json(bookmarks)?;
+
+        let mut command = Command::new(&self.interpreter);
+        command
+            .arg(&self.path)
+            .arg(output_path)
+            .args(&self.options.args)
+            .envs(&self.options.env);
+        if let Some(working_dir) = &self.options.working_dir {
+            command.current_dir(working_dir);
+        }
+
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| {
+                ExporterError::PythonError(format!("Failed to run Python script: {}", e))
+            })?;
+
+        // Both are piped, so they are always there
+        let stdin = child.stdin.take().unwrap();
+        let stderr = log_stderr_in_background(
+            format!("Exporter {}", self.name),
+            child.stderr.take().unwrap(),
+        );
+        let child = Arc::new(Mutex::new(child));
+        let watchdog = self.options.timeout_secs.map(|secs| {
+            let child = child.clone();
+            // Exports can't be cancelled, only the timeout stops them
+            Watchdog::start(
+                Some(Duration::from_secs(secs)),
+                &CancelFlag::default(),
+                move || {
+                    let _ = child.lock().unwrap().kill();
+                },
+            )
+        });
+
+        // Dropping stdin closes it, so the script sees the end of the input.
+        // A script that fails before reading everything breaks the pipe, its stderr explains more.
+        let written = { stdin }.write_all(input.as_bytes());
+
+        let status = wait_for_exit(&child)?;
+        let timed_out = watchdog.is_some_and(|watchdog| watchdog.stop());
+        let error = stderr.join().unwrap_or_default();
+
+        if timed_out {
+            return Err(ExporterError::PythonError(format!(
+                "Python script timed out after {} seconds",
+                self.options.timeout_secs.unwrap_or_default()
+            )));
+        }
+        if !status.success() {
+            return Err(ExporterError::PythonError(format!(
+                "Python script error: {}",
+                error
+            )));
+        }
+
+        Ok(written?)
+    }
+
+    fn format(&self) -> &str {
+        &self.format
+    }
+
+    fn info(&self) -> ExporterConfig {
+        ExporterConfig {
+            name: self.name.to_string(),
+            r#type: self.r#type.to_string(),
+            path: self.path.to_string(),
+            format: self.format.to_string(),
+            script: self.options.clone(),
+        }
+    }
+}
+
+// The exporters PcPocket ships with
+pub struct BuiltinExporter {
+    name: String,
+    r#type: String,
+    path: String,
+    format: String,
+    export_format: ExportFormat,
+}
+
+impl BuiltinExporter {
+    pub fn new(export_format: ExportFormat) -> Self {
+        let (name, format) = match export_format {
+            ExportFormat::NetscapeHtml => ("Default HTML", "html"),
+            ExportFormat::PcPocketJson => ("Default JSON", "json"),
+            ExportFormat::Csv => ("Default CSV", "csv"),
+            ExportFormat::Markdown => ("Default Markdown", "md"),
+        };
+
+        BuiltinExporter {
+            name: name.to_string(),
+            r#type: "default".to_string(),
+            path: "In app".to_string(),
+            format: format.to_string(),
+            export_format,
+        }
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Folder of the Netscape export, keyed by the segments of the tag paths
+#[derive(Default)]
+struct ExportFolder<'a> {
+    folders: BTreeMap<&'a str, ExportFolder<'a>>,
+    bookmarks: Vec<&'a BookmarkWithTags>,
+}
+
+impl<'a> ExportFolder<'a> {
+    // A bookmark goes in the folder of its most nested tag, all of its tags are kept in TAGS
+    fn insert(&mut self, bookmark: &'a BookmarkWithTags) {
+        let folder_tag = bookmark
+            .tags
+            .iter()
+            .max_by_key(|tag| (tag.matches(TAG_SEPARATOR).count(), std::cmp::Reverse(*tag)));
+
+        let mut folder = self;
+        for segment in folder_tag
+            .into_iter()
+            .flat_map(|tag| tag.split(TAG_SEPARATOR))
+            .filter(|segment| !segment.is_empty())
+        {
+            folder = folder.folders.entry(segment).or_default();
+        }
+        folder.bookmarks.push(bookmark);
+    }
+
+    fn render(&self, output: &mut String, depth: usize) {
+        let indent = "    ".repeat(depth + 1);
+
+        for (name, folder) in &self.folders {
+            output.push_str(&format!("{}<DT><H3>{}</H3>\n", indent, escape_html(name)));
+            output.push_str(&format!("{}<DL><p>\n", indent));
+            folder.render(output, depth + 1);
+            output.push_str(&format!("{}</DL><p>\n", indent));
+        }
+
+        for entry in &self.bookmarks {
+            let bookmark = &entry.bookmark;
+            let mut attributes = format!(
+                "HREF=\"{}\" ADD_DATE=\"{}\"",
+                escape_html(&bookmark.link),
+                bookmark.created_at
+            );
+            if let Some(icon) = bookmark
+                .icon_link
+                .as_deref()
+                .filter(|icon| !icon.is_empty())
+            {
+                // Firefox keeps inline icons in ICON and remote ones in ICON_URI
+                let attribute = if icon.starts_with("data:") {
+                    "ICON"
+                } else {
+                    "ICON_URI"
+                };
+                attributes.push_str(&format!(" {}=\"{}\"", attribute, escape_html(icon)));
+            }
+            if !entry.tags.is_empty() {
+                attributes.push_str(&format!(" TAGS=\"{}\"", escape_html(&entry.tags.join(","))));
+            }
+
+            output.push_str(&format!(
+                "{}<DT><A {}>{}</A>\n",
+                indent,
+                attributes,
+                escape_html(bookmark.title.as_deref().unwrap_or(&bookmark.link))
+            ));
+        }
+    }
+}
+
+fn render_netscape_html(bookmarks: &[BookmarkWithTags]) -> String {
+    let mut root = ExportFolder::default();
+    for bookmark in bookmarks {
+        root.insert(bookmark);
+    }
+
+    let mut output = String::from(
+        "<!DOCTYPE NETSCAPE-Bookmark-file-1>\n\
+         <!-- This is an automatically generated file.\n     \
+         It will be read and overwritten.\n     \
+         DO NOT EDIT! -->\n\
+         <META HTTP-EQUIV=\"Content-Type\" CONTENT=\"text/html; charset=UTF-8\">\n\
+         <TITLE>Bookmarks</TITLE>\n\
+         <H1>Bookmarks</H1>\n\
+         <DL><p>\n",
+    );
+    root.render(&mut output, 0);
+    output.push_str("</DL><p>\n");
+    output
+}
+
+fn render_pcpocket_json(bookmarks: &[BookmarkWithTags]) -> Result<String, ExporterError> {
+    let successful: Vec<ParsedBookmarkWithTags> = bookmarks
+        .iter()
+        .map(|entry| ParsedBookmarkWithTags {
+            bookmark: BookmarkNew {
+                title: entry.bookmark.title.clone(),
+                link: entry.bookmark.link.clone(),
+                icon_link: entry.bookmark.icon_link.clone(),
+                created_at: entry.bookmark.created_at,
+            },
+            tags: entry.tags.clone(),
+        })
+        .collect();
+
+    serde_json::to_string_pretty(&ParserSuccess::new(successful, vec![]))
+        .map_err(|e| ExporterError::Serialization(e.to_string()))
+}
+
+// title,link,icon_link,created_at,tags with the tags separated by commas
+fn render_csv(bookmarks: &[BookmarkWithTags]) -> Result<String, ExporterError> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    let serialize_error = |e: csv::Error| ExporterError::Serialization(e.to_string());
+
+    writer
+        .write_record(["title", "link", "icon_link", "created_at", "tags"])
+        .map_err(serialize_error)?;
+    for entry in bookmarks {
+        let bookmark = &entry.bookmark;
+        writer
+            .write_record([
+                bookmark.title.as_deref().unwrap_or_default(),
+                &bookmark.link,
+                bookmark.icon_link.as_deref().unwrap_or_default(),
+                &bookmark.created_at.to_string(),
+                &entry.tags.join(","),
+            ])
+            .map_err(serialize_error)?;
+    }
+
+    let output = writer
+        .into_inner()
+        .map_err(|e| ExporterError::Serialization(e.to_string()))?;
+    String::from_utf8(output).map_err(|e| ExporterError::Serialization(e.to_string()))
+}
+
+// A list of links, each followed by its tags as inline code
+fn render_markdown(bookmarks: &[BookmarkWithTags]) -> String {
+    let mut output = String::from("# Bookmarks\n\n");
+
+    for entry in bookmarks {
+        let bookmark = &entry.bookmark;
+        let title = bookmark
+            .title
+            .as_deref()
+            .filter(|title| !title.trim().is_empty())
+            .unwrap_or(&bookmark.link)
+            .replace('\\', "\\\\")
+            .replace('[', "\\[")
+            .replace(']', "\\]");
+        // Angle brackets let the link contain spaces and parentheses
+        let link = bookmark.link.replace('<', "%3C").replace('>', "%3E");
+
+        output.push_str(&format!("- [{}](<{}>)", title, link));
+        for tag in &entry.tags {
+            output.push_str(&format!(" `{}`", tag.replace('`', "'")));
+        }
+        output.push('\n');
+    }
+
+    output
+}
+
+impl Exporter for BuiltinExporter {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn export(
+        &self,
+        bookmarks: &[BookmarkWithTags],
+        output_path: &str,
+    ) -> Result<(), ExporterError> {
+        let content = match self.export_format {
+            ExportFormat::NetscapeHtml => render_netscape_html(bookmarks),
+            ExportFormat::PcPocketJson => render_pcpocket_json(bookmarks)?,
+            ExportFormat::Csv => render_csv(bookmarks)?,
+            ExportFormat::Markdown => render_markdown(bookmarks),
+        };
+
+        fs::write(output_path, content)
+            .map_err(|e| ExporterError::FileWriteError(format!("Error writing file: {}", e)))
+    }
+
+    fn format(&self) -> &str {
+        &self.format
+    }
+
+    fn info(&self) -> ExporterConfig {
+        ExporterConfig {
+            name: self.name.to_string(),
+            r#type: self.r#type.to_string(),
+            path: self.path.to_string(),
+            format: self.format.to_string(),
+            script: ExporterOptions::default(),
+        }
+    }
+}
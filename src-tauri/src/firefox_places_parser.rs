@@ -0,0 +1,306 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Integer, Nullable, Text};
+use time::OffsetDateTime;
+
+use crate::custom_parsers::{Parser, ParserSuccess};
+use crate::models::BookmarkNew;
+use crate::parser_errors::ParserError;
+use crate::structs::{ParseFailBookmark, ParsedBookmarkWithTags, ParserConfig, ScriptOptions};
+use crate::tag_tree::TAG_SEPARATOR;
+
+const ROOT_GUID: &str = "root________";
+// Built-in folders right below the root ("Bookmarks Toolbar", "Bookmarks Menu", ...).
+// Their titles are localized and say nothing about the bookmark, they don't become tags.
+const BUILT_IN_ROOT_GUIDS: &[&str] = &[
+    "toolbar_____",
+    "menu________",
+    "unfiled_____",
+    "mobile______",
+];
+// Firefox keeps tags as folders below this root, one folder per tag
+const TAGS_ROOT_GUID: &str = "tags________";
+
+#[derive(QueryableByName)]
+struct PlacesFolder {
+    #[diesel(sql_type = Integer)]
+    id: i32,
+    #[diesel(sql_type = Nullable<Integer>)]
+    parent: Option<i32>,
+    #[diesel(sql_type = Nullable<Text>)]
+    title: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    guid: Option<String>,
+}
+
+#[derive(QueryableByName)]
+struct PlacesBookmark {
+    #[diesel(sql_type = Integer)]
+    id: i32,
+    #[diesel(sql_type = Nullable<Integer>)]
+    parent: Option<i32>,
+    #[diesel(sql_type = Nullable<Integer>)]
+    place_id: Option<i32>,
+    #[diesel(sql_type = Nullable<Text>)]
+    title: Option<String>,
+    #[diesel(sql_type = Nullable<BigInt>)]
+    date_added: Option<i64>,
+    #[diesel(sql_type = Nullable<Text>)]
+    url: Option<String>,
+}
+
+#[derive(QueryableByName)]
+struct PageIcon {
+    #[diesel(sql_type = Text)]
+    page_url: String,
+    #[diesel(sql_type = Text)]
+    icon_url: String,
+}
+
+// Copy of a Firefox database (with its write-ahead log) in a temporary directory.
+// Firefox keeps its databases locked while running, the copy can be read freely.
+// The directory is removed when the copy is dropped.
+struct TemporaryCopy {
+    directory: PathBuf,
+    database: PathBuf,
+}
+
+impl TemporaryCopy {
+    fn new(database: &Path) -> std::io::Result<Self> {
+        let directory = std::env::temp_dir().join(format!(
+            "pcpocket-firefox-{}-{}",
+            std::process::id(),
+            OffsetDateTime::now_utc().unix_timestamp_nanos()
+        ));
+        fs::create_dir_all(&directory)?;
+
+        let file_name = database.file_name().unwrap_or_default();
+        let copy = TemporaryCopy {
+            database: directory.join(file_name),
+            directory,
+        };
+        fs::copy(database, &copy.database)?;
+
+        let mut wal = database.as_os_str().to_owned();
+        wal.push("-wal");
+        let wal = PathBuf::from(wal);
+        if wal.exists() {
+            let mut wal_copy = copy.database.as_os_str().to_owned();
+            wal_copy.push("-wal");
+            fs::copy(&wal, PathBuf::from(wal_copy))?;
+        }
+
+        Ok(copy)
+    }
+
+    fn connect(&self) -> ConnectionResult<SqliteConnection> {
+        SqliteConnection::establish(&self.database.to_string_lossy())
+    }
+}
+
+impl Drop for TemporaryCopy {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.directory);
+    }
+}
+
+// Largest favicon of every page from favicons.sqlite next to places.sqlite, if there is one
+fn load_favicons(places_path: &Path) -> HashMap<String, String> {
+    let favicons_path = places_path.with_file_name("favicons.sqlite");
+    if !favicons_path.exists() {
+        return HashMap::new();
+    }
+
+    let icons = TemporaryCopy::new(&favicons_path)
+        .ok()
+        .and_then(|copy| {
+            let mut conn = copy.connect().ok()?;
+            diesel::sql_query(
+                "SELECT moz_pages_w_icons.page_url, moz_icons.icon_url
+                 FROM moz_pages_w_icons
+                 INNER JOIN moz_icons_to_pages ON moz_icons_to_pages.page_id = moz_pages_w_icons.id
+                 INNER JOIN moz_icons ON moz_icons.id = moz_icons_to_pages.icon_id
+                 ORDER BY moz_icons.width ASC",
+            )
+            .load::<PageIcon>(&mut conn)
+            .ok()
+        })
+        .unwrap_or_default();
+
+    // Ordered by width, so the largest icon of a page is inserted last
+    icons
+        .into_iter()
+        .map(|icon| (icon.page_url, icon.icon_url))
+        .collect()
+}
+
+// Parser reading bookmarks straight from places.sqlite in a Firefox profile.
+// Folder ancestry becomes a nested tag and Firefox's own tags are kept as tags.
+pub struct FirefoxPlacesParser {
+    name: String,
+    r#type: String,
+    path: String,
+    supported_formats: Vec<String>,
+}
+
+impl FirefoxPlacesParser {
+    pub fn new() -> Self {
+        FirefoxPlacesParser {
+            name: "Default Firefox".to_string(),
+            r#type: "default".to_string(),
+            path: "In app".to_string(),
+            supported_formats: vec!["sqlite".to_string()],
+        }
+    }
+}
+
+impl Parser for FirefoxPlacesParser {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn parse(&self, input_path: &str) -> Result<ParserSuccess, ParserError> {
+        let places_path = Path::new(input_path);
+        let copy = TemporaryCopy::new(places_path)
+            .map_err(|e| ParserError::FileReadError(format!("Error copying database: {}", e)))?;
+        let mut conn = copy
+            .connect()
+            .map_err(|e| ParserError::FileReadError(format!("Error opening database: {}", e)))?;
+
+        let invalid = |e: diesel::result::Error| {
+            ParserError::InvalidFormat(format!("Not a Firefox places database: {}", e))
+        };
+
+        let folders: HashMap<i32, PlacesFolder> =
+            diesel::sql_query("SELECT id, parent, title, guid FROM moz_bookmarks WHERE type = 2")
+                .load::<PlacesFolder>(&mut conn)
+                .map_err(invalid)?
+                .into_iter()
+                .map(|folder| (folder.id, folder))
+                .collect();
+
+        let entries = diesel::sql_query(
+            "SELECT moz_bookmarks.id, moz_bookmarks.parent, moz_bookmarks.fk AS place_id,
+                    moz_bookmarks.title, moz_bookmarks.dateAdded AS date_added, moz_places.url
+             FROM moz_bookmarks
+             LEFT JOIN moz_places ON moz_places.id = moz_bookmarks.fk
+             WHERE moz_bookmarks.type = 1
+             ORDER BY moz_bookmarks.parent, moz_bookmarks.position",
+        )
+        .load::<PlacesBookmark>(&mut conn)
+        .map_err(invalid)?;
+
+        let favicons = load_favicons(places_path);
+
+        // Folder titles from the top down, None when the entry sits in the tags root.
+        // A damaged database can have parents pointing in a circle, the walk stops at the first repeat.
+        let ancestry = |mut parent: Option<i32>| -> Option<Vec<String>> {
+            let mut path: Vec<String> = Vec::new();
+            let mut visited: HashSet<i32> = HashSet::new();
+            while let Some(folder) = parent.and_then(|parent| folders.get(&parent)) {
+                if !visited.insert(folder.id) {
+                    break;
+                }
+                match folder.guid.as_deref() {
+                    Some(TAGS_ROOT_GUID) => return None,
+                    Some(ROOT_GUID) => break,
+                    Some(guid) if BUILT_IN_ROOT_GUIDS.contains(&guid) => break,
+                    _ => {}
+                }
+                if let Some(title) = folder.title.as_deref().map(str::trim) {
+                    if !title.is_empty() {
+                        path.push(title.to_string());
+                    }
+                }
+                parent = folder.parent;
+            }
+            path.reverse();
+            Some(path)
+        };
+
+        // Entries below the tags root only mark their place with the tag folder's title
+        let mut place_tags: HashMap<i32, Vec<String>> = HashMap::new();
+        let mut bookmarks: Vec<(PlacesBookmark, Vec<String>)> = Vec::new();
+        for entry in entries {
+            match ancestry(entry.parent) {
+                Some(path) => bookmarks.push((entry, path)),
+                None => {
+                    let tag = entry
+                        .parent
+                        .and_then(|parent| folders.get(&parent))
+                        .and_then(|folder| folder.title.clone());
+                    if let (Some(place_id), Some(tag)) = (entry.place_id, tag) {
+                        place_tags.entry(place_id).or_default().push(tag);
+                    }
+                }
+            }
+        }
+
+        let mut successful: Vec<ParsedBookmarkWithTags> = Vec::new();
+        let mut failed: Vec<ParseFailBookmark> = Vec::new();
+
+        for (index, (entry, path)) in bookmarks.into_iter().enumerate() {
+            let link = match entry.url.as_deref() {
+                // Smart bookmarks are saved searches over the history, not links
+                Some(url) if url.starts_with("place:") => {
+                    failed.push(ParseFailBookmark::new(
+                        index as i32,
+                        url.to_string(),
+                        "Firefox query bookmark, not a link".to_string(),
+                    ));
+                    continue;
+                }
+                Some(url) if !url.is_empty() => url.to_string(),
+                _ => {
+                    failed.push(ParseFailBookmark::new(
+                        index as i32,
+                        format!("moz_bookmarks.id = {}", entry.id),
+                        "Bookmark has no URL".to_string(),
+                    ));
+                    continue;
+                }
+            };
+
+            let mut tags: Vec<String> = Vec::new();
+            if !path.is_empty() {
+                tags.push(path.join(&TAG_SEPARATOR.to_string()));
+            }
+            if let Some(firefox_tags) = entry.place_id.and_then(|place| place_tags.get(&place)) {
+                tags.extend(firefox_tags.iter().cloned());
+            }
+
+            successful.push(ParsedBookmarkWithTags {
+                bookmark: BookmarkNew {
+                    title: entry.title,
+                    icon_link: favicons.get(&link).cloned(),
+                    link,
+                    // dateAdded counts microseconds
+                    created_at: entry
+                        .date_added
+                        .map(|date_added| date_added / 1_000_000)
+                        .unwrap_or_else(|| OffsetDateTime::now_utc().unix_timestamp()),
+                },
+                tags,
+            });
+        }
+
+        Ok(ParserSuccess::new(successful, failed))
+    }
+
+    fn supported_formats(&self) -> Vec<&str> {
+        self.supported_formats.iter().map(|s| s.as_str()).collect()
+    }
+
+    fn info(&self) -> ParserConfig {
+        ParserConfig {
+            name: self.name.to_string(),
+            r#type: self.r#type.to_string(),
+            path: self.path.to_string(),
+            supported_formats: self.supported_formats.clone(),
+            script: ScriptOptions::default(),
+        }
+    }
+}
@@ -0,0 +1,281 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use diesel::dsl::{exists, not};
+use diesel::prelude::*;
+use serde::Serialize;
+
+use crate::database_cmds::add_bookmark_tags;
+use crate::models::{ImportNew, ImportOverwrite};
+use crate::structs::InsertCounts;
+
+// Where the bookmarks of an import come from
+#[derive(Debug, Clone)]
+pub struct ImportSource {
+    pub source_file: String,
+    pub parser_name: String,
+    // Entries the parser couldn't read
+    pub failed: usize,
+}
+
+// Tags looked up or deleted per query, stays below SQLite's variable limit
+const TAGS_CHUNK_SIZE: usize = 500;
+
+diesel::alias!(crate::schema::tags as child_tags: ChildTags);
+diesel::alias!(crate::schema::import_overwrites as own_overwrites: OwnOverwrites);
+
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RollbackCounts {
+    pub removed: usize,
+    pub restored: usize,
+    pub untagged: usize,
+    // Tags left without bookmarks by the rollback
+    pub deleted_tags: usize,
+}
+
+#[derive(Debug)]
+pub enum RollbackError {
+    Database(diesel::result::Error),
+    // A later import overwrote bookmarks this one created or overwrote, and has to be
+    // rolled back first. Its snapshot holds this import's values, not the original ones.
+    OverwrittenLater(i32),
+}
+
+impl fmt::Display for RollbackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RollbackError::Database(e) => write!(f, "{}", e),
+            RollbackError::OverwrittenLater(later) => write!(
+                f,
+                "import {} changed the same bookmarks afterwards, roll it back first",
+                later
+            ),
+        }
+    }
+}
+
+impl From<diesel::result::Error> for RollbackError {
+    fn from(err: diesel::result::Error) -> Self {
+        RollbackError::Database(err)
+    }
+}
+
+// Record the start of an import, the returned id is stamped on everything it adds
+pub fn start_import(conn: &mut SqliteConnection, source: &ImportSource) -> QueryResult<i32> {
+    use crate::schema::imports;
+
+    diesel::insert_into(imports::table)
+        .values(ImportNew {
+            source_file: &source.source_file,
+            parser_name: &source.parser_name,
+            failed: source.failed as i32,
+        })
+        .returning(imports::id)
+        .get_result(conn)
+}
+
+pub fn finish_import(
+    conn: &mut SqliteConnection,
+    import: i32,
+    counts: &InsertCounts,
+) -> QueryResult<()> {
+    use crate::schema::imports;
+
+    diesel::update(imports::table.find(import))
+        .set((
+            imports::inserted.eq(counts.inserted as i32),
+            imports::skipped.eq(counts.skipped as i32),
+            imports::merged.eq(counts.merged as i32),
+            imports::updated.eq(counts.updated as i32),
+        ))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+// Keep a bookmark as it is before an import overwrites it, so the import can be undone.
+// Only the first state is kept when one import overwrites a bookmark more than once.
+pub fn snapshot_bookmark(
+    conn: &mut SqliteConnection,
+    import: i32,
+    snapshot_bookmark_id: i32,
+) -> QueryResult<()> {
+    use crate::schema::{bookmark_tags, bookmarks_table, import_overwrites, tags};
+
+    let (title, link, icon_link, created_at, canonical_link) = bookmarks_table::table
+        .find(snapshot_bookmark_id)
+        .select((
+            bookmarks_table::title,
+            bookmarks_table::link,
+            bookmarks_table::icon_link,
+            bookmarks_table::created_at,
+            bookmarks_table::canonical_link,
+        ))
+        .first::<(Option<String>, String, Option<String>, i64, String)>(conn)?;
+
+    let tag_names: Vec<String> = bookmark_tags::table
+        .inner_join(tags::table)
+        .filter(bookmark_tags::bookmark_id.eq(snapshot_bookmark_id))
+        .select(tags::name)
+        .load(conn)?;
+
+    diesel::insert_or_ignore_into(import_overwrites::table)
+        .values(ImportOverwrite {
+            import_id: import,
+            bookmark_id: snapshot_bookmark_id,
+            title,
+            link,
+            icon_link,
+            created_at,
+            canonical_link,
+            tags: serde_json::to_string(&tag_names).unwrap_or_else(|_| "[]".to_string()),
+        })
+        .execute(conn)?;
+
+    Ok(())
+}
+
+// Undo an import, must be run inside a transaction. Bookmarks it created are deleted,
+// tags it added to existing bookmarks are removed and bookmarks it overwrote get
+// their old values and tags back. Tags left without bookmarks and the import's record go as well.
+pub fn rollback_import(
+    conn: &mut SqliteConnection,
+    import: i32,
+) -> Result<RollbackCounts, RollbackError> {
+    use crate::schema::{bookmark_tags, bookmarks_table, import_overwrites, imports};
+
+    // Fail on unknown ids instead of silently doing nothing
+    imports::table
+        .find(import)
+        .select(imports::id)
+        .first::<i32>(conn)?;
+
+    let created = bookmarks_table::table
+        .filter(bookmarks_table::import_id.eq(import))
+        .select(bookmarks_table::id);
+    let overwritten = import_overwrites::table
+        .filter(import_overwrites::import_id.eq(import))
+        .select(import_overwrites::bookmark_id);
+
+    let later_import = import_overwrites::table
+        .filter(import_overwrites::import_id.gt(import))
+        .filter(
+            import_overwrites::bookmark_id
+                .eq_any(created)
+                .or(import_overwrites::bookmark_id.eq_any(
+                    own_overwrites
+                        .filter(
+                            own_overwrites
+                                .field(import_overwrites::import_id)
+                                .eq(import),
+                        )
+                        .select(own_overwrites.field(import_overwrites::bookmark_id)),
+                )),
+        )
+        .select(import_overwrites::import_id)
+        .order(import_overwrites::import_id.desc())
+        .first::<i32>(conn)
+        .optional()?;
+    if let Some(later) = later_import {
+        return Err(RollbackError::OverwrittenLater(later));
+    }
+
+    // Every tag the rollback takes off a bookmark, checked for remaining bookmarks at the end
+    let touched_tags: HashSet<i32> = bookmark_tags::table
+        .filter(
+            bookmark_tags::import_id
+                .eq(import)
+                .or(bookmark_tags::bookmark_id.eq_any(created))
+                .or(bookmark_tags::bookmark_id.eq_any(overwritten)),
+        )
+        .select(bookmark_tags::tag_id)
+        .distinct()
+        .load::<i32>(conn)?
+        .into_iter()
+        .collect();
+
+    // Deleting the bookmarks cascades to their bookmark_tags rows
+    let removed =
+        diesel::delete(bookmarks_table::table.filter(bookmarks_table::import_id.eq(import)))
+            .execute(conn)?;
+
+    let untagged = diesel::delete(bookmark_tags::table.filter(bookmark_tags::import_id.eq(import)))
+        .execute(conn)?;
+
+    let overwrites: Vec<ImportOverwrite> = import_overwrites::table
+        .filter(import_overwrites::import_id.eq(import))
+        .select(ImportOverwrite::as_select())
+        .load(conn)?;
+
+    let mut restored = 0;
+    for overwrite in overwrites {
+        let updated = diesel::update(bookmarks_table::table.find(overwrite.bookmark_id))
+            .set((
+                bookmarks_table::title.eq(&overwrite.title),
+                bookmarks_table::link.eq(&overwrite.link),
+                bookmarks_table::icon_link.eq(&overwrite.icon_link),
+                bookmarks_table::created_at.eq(overwrite.created_at),
+                bookmarks_table::canonical_link.eq(&overwrite.canonical_link),
+            ))
+            .execute(conn)?;
+        // Deleted since the import, nothing to restore
+        if updated == 0 {
+            continue;
+        }
+
+        diesel::delete(
+            bookmark_tags::table.filter(bookmark_tags::bookmark_id.eq(overwrite.bookmark_id)),
+        )
+        .execute(conn)?;
+        let tag_names: Vec<String> = serde_json::from_str(&overwrite.tags).unwrap_or_default();
+        add_bookmark_tags(conn, overwrite.bookmark_id, &tag_names, None)?;
+        restored += 1;
+    }
+
+    let deleted_tags = delete_unused_tags(conn, touched_tags.into_iter().collect())?;
+
+    // Deleting the import cascades to its import_overwrites rows
+    diesel::delete(imports::table.find(import)).execute(conn)?;
+
+    Ok(RollbackCounts {
+        removed,
+        restored,
+        untagged,
+        deleted_tags,
+    })
+}
+
+// Delete the tags of `candidates` that have neither bookmarks nor nested tags.
+// Parents of deleted tags are checked next, they may have lost their last child.
+fn delete_unused_tags(conn: &mut SqliteConnection, candidates: Vec<i32>) -> QueryResult<usize> {
+    use crate::schema::{bookmark_tags, tags};
+
+    let mut deleted = 0;
+    let mut candidates = candidates;
+    while !candidates.is_empty() {
+        let mut parents: HashSet<i32> = HashSet::new();
+
+        for chunk in candidates.chunks(TAGS_CHUNK_SIZE) {
+            let unused: Vec<(i32, Option<i32>)> = tags::table
+                .filter(tags::id.eq_any(chunk))
+                .filter(not(exists(
+                    bookmark_tags::table.filter(bookmark_tags::tag_id.eq(tags::id)),
+                )))
+                .filter(not(exists(child_tags.filter(
+                    child_tags.field(tags::parent_id).eq(tags::id.nullable()),
+                ))))
+                .select((tags::id, tags::parent_id))
+                .load(conn)?;
+
+            let unused_ids: Vec<i32> = unused.iter().map(|(id, _)| *id).collect();
+            deleted +=
+                diesel::delete(tags::table.filter(tags::id.eq_any(&unused_ids))).execute(conn)?;
+            parents.extend(unused.into_iter().filter_map(|(_, parent_id)| parent_id));
+        }
+
+        candidates = parents.into_iter().collect();
+    }
+
+    Ok(deleted)
+}
@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::custom_parsers::{CancelFlag, Parser};
+use crate::database_cmds::{get_connection, insert_import};
+use crate::import_batches::ImportSource;
+use crate::structs::{AppData, DuplicatePolicy, InsertCounts, ParsedBookmarkWithTags};
+use crate::utils::broadcast_info;
+
+// Progress is reported after this many bookmarks, every bookmark would flood the frontend
+const PROGRESS_INTERVAL: usize = 100;
+// Finished jobs stay queryable for this long, then they are dropped
+const FINISHED_JOB_TTL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportJobStatus {
+    Parsing,
+    Inserting,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+// State of an import job, also the payload of the import-progress event
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportJob {
+    pub job_id: u32,
+    pub file_path: String,
+    pub parser_name: String,
+    pub status: ImportJobStatus,
+    // Entries the parser read, and the ones it couldn't
+    pub parsed: usize,
+    pub failed: usize,
+    // What happened to the parsed entries so far
+    pub counts: InsertCounts,
+    pub error: Option<String>,
+}
+
+pub struct ImportJobControl {
+    // Shared with the parser, which stops when the job is cancelled while parsing
+    cancel: CancelFlag,
+    job: Mutex<ImportJob>,
+    // When the job completed, failed or was cancelled
+    finished: Mutex<Option<Instant>>,
+}
+
+impl ImportJobControl {
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    pub fn snapshot(&self) -> ImportJob {
+        self.job.lock().unwrap().clone()
+    }
+
+    fn is_expired(&self) -> bool {
+        self.finished
+            .lock()
+            .unwrap()
+            .is_some_and(|finished| finished.elapsed() >= FINISHED_JOB_TTL)
+    }
+
+    // Change the job's state and tell the frontend about it
+    fn update(&self, app: &AppHandle, change: impl FnOnce(&mut ImportJob)) {
+        let job = {
+            let mut job = self.job.lock().unwrap();
+            change(&mut job);
+            job.clone()
+        };
+        if matches!(
+            job.status,
+            ImportJobStatus::Completed | ImportJobStatus::Failed | ImportJobStatus::Cancelled
+        ) {
+            self.finished
+                .lock()
+                .unwrap()
+                .get_or_insert_with(Instant::now);
+        }
+        app.emit("import-progress", job).unwrap();
+    }
+}
+
+// The import jobs of this session. Finished ones are kept for FINISHED_JOB_TTL so their
+// status stays queryable.
+pub struct ImportJobs {
+    jobs: HashMap<u32, Arc<ImportJobControl>>,
+    next_id: u32,
+}
+
+impl ImportJobs {
+    pub fn new() -> Self {
+        Self {
+            jobs: HashMap::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn open(&mut self, file_path: String, parser_name: String) -> Arc<ImportJobControl> {
+        self.evict_expired();
+        let job_id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        let control = Arc::new(ImportJobControl {
+            cancel: CancelFlag::default(),
+            finished: Mutex::new(None),
+            job: Mutex::new(ImportJob {
+                job_id,
+                file_path,
+                parser_name,
+                status: ImportJobStatus::Parsing,
+                parsed: 0,
+                failed: 0,
+                counts: InsertCounts::default(),
+                error: None,
+            }),
+        });
+        self.jobs.insert(job_id, control.clone());
+        control
+    }
+
+    pub fn get(&self, job_id: u32) -> Option<Arc<ImportJobControl>> {
+        self.jobs.get(&job_id).cloned()
+    }
+
+    pub fn list(&mut self) -> Vec<ImportJob> {
+        self.evict_expired();
+        let mut jobs: Vec<ImportJob> = self.jobs.values().map(|job| job.snapshot()).collect();
+        jobs.sort_by_key(|job| job.job_id);
+        jobs
+    }
+
+    fn evict_expired(&mut self) {
+        self.jobs.retain(|_, job| !job.is_expired());
+    }
+}
+
+fn fail(app: &AppHandle, control: &ImportJobControl, error: String) {
+    broadcast_info("Bookmarks Import Error", &error, log::Level::Error, true);
+    control.update(app, |job| {
+        job.status = ImportJobStatus::Failed;
+        job.error = Some(error);
+    });
+}
+
+fn cancelled(app: &AppHandle, control: &ImportJobControl) {
+    broadcast_info(
+        "Bookmarks Import Cancelled",
+        "The import was cancelled, no bookmarks were changed",
+        log::Level::Info,
+        false,
+    );
+    control.update(app, |job| job.status = ImportJobStatus::Cancelled);
+}
+
+// Parse and insert the file of a job. Runs on a blocking thread, neither the parser
+// registry nor the app state are locked while parsing or inserting.
+pub fn run_import_job(app: AppHandle, parser: Arc<dyn Parser>, control: Arc<ImportJobControl>) {
+    let job = control.snapshot();
+    control.update(&app, |_| {});
+
+    // Parsers that report progress update the parsed count while they run
+    let parsed = match parser.parse_with_progress(
+        &job.file_path,
+        &mut |update| control.update(&app, |job| job.parsed = update.processed),
+        &control.cancel,
+    ) {
+        Ok(parsed) => parsed,
+        Err(_) if control.is_cancelled() => {
+            cancelled(&app, &control);
+            return;
+        }
+        Err(e) => {
+            fail(&app, &control, format!("Error parsing bookmarks: {}", e));
+            return;
+        }
+    };
+    if control.is_cancelled() {
+        cancelled(&app, &control);
+        return;
+    }
+
+    let successful = parsed.get_successful();
+    let failed_count = parsed.get_failed().len();
+    control.update(&app, |job| {
+        job.status = ImportJobStatus::Inserting;
+        job.parsed = successful.len();
+        job.failed = failed_count;
+    });
+
+    let (pool, tracking_params, policy) = {
+        let binding = app.state::<Mutex<AppData>>();
+        let app_data = binding.lock().unwrap();
+        (
+            app_data.db_pool.clone(),
+            app_data.tracking_params.clone(),
+            app_data.duplicate_policy,
+        )
+    };
+    let items: Vec<(&ParsedBookmarkWithTags, DuplicatePolicy)> = successful
+        .iter()
+        .map(|bookmark_with_tags| (bookmark_with_tags, policy))
+        .collect();
+    let source = ImportSource {
+        source_file: job.file_path,
+        parser_name: job.parser_name,
+        failed: failed_count,
+    };
+
+    let mut conn = get_connection(&pool);
+    let result = insert_import(&mut conn, &items, &source, &tracking_params, |counts| {
+        if counts.processed() % PROGRESS_INTERVAL == 0 {
+            control.update(&app, |job| job.counts = counts.clone());
+        }
+        !control.is_cancelled()
+    });
+
+    match result {
+        Ok(counts) => {
+            app.emit("bookmarks-updated", "bookmarks-updated").unwrap();
+            broadcast_info(
+                "Bookmarks Imported",
+                &format!(
+                    "Successfully imported {} bookmarks ({} duplicates merged, {} updated, {} skipped)",
+                    counts.inserted, counts.merged, counts.updated, counts.skipped
+                ),
+                log::Level::Info,
+                false,
+            );
+            if failed_count > 0 {
+                broadcast_info(
+                    "Bookmarks Import Warning",
+                    &format!(
+                        "{} entries could not be read and were left out",
+                        failed_count
+                    ),
+                    log::Level::Warn,
+                    true,
+                );
+            }
+            control.update(&app, |job| {
+                job.status = ImportJobStatus::Completed;
+                job.counts = counts;
+            });
+        }
+        Err(diesel::result::Error::RollbackTransaction) if control.is_cancelled() => {
+            cancelled(&app, &control);
+        }
+        Err(e) => fail(&app, &control, format!("Error inserting bookmarks: {}", e)),
+    }
+}
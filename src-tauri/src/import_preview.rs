@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::commands::attach_tags;
+use crate::database_cmds::DbConnection;
+use crate::import_batches::ImportSource;
+use crate::models::Bookmark;
+use crate::structs::{
+    BookmarkWithTags, DuplicatePolicy, ParseFailBookmark, ParsedBookmarkWithTags,
+};
+use crate::url_canonical::canonicalize_url;
+
+// Stay well below SQLite's limit on bound parameters
+const LOOKUP_CHUNK_SIZE: usize = 500;
+// Previews that are neither committed nor cancelled in this time are dropped,
+// so an abandoned preview doesn't keep its parsed file in memory
+const PREVIEW_TTL: Duration = Duration::from_secs(60 * 60);
+
+// A parsed import waiting for the user to pick what gets committed
+pub struct PendingImport {
+    pub source: ImportSource,
+    pub parsed: Vec<ParsedBookmarkWithTags>,
+}
+
+pub struct ImportPreviews {
+    // Each preview with the time it was opened
+    previews: HashMap<u32, (PendingImport, Instant)>,
+    next_id: u32,
+}
+
+impl ImportPreviews {
+    pub fn new() -> Self {
+        Self {
+            previews: HashMap::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn open(&mut self, pending: PendingImport) -> u32 {
+        self.evict_expired();
+        let preview_id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        self.previews.insert(preview_id, (pending, Instant::now()));
+        preview_id
+    }
+
+    // The parsed items are handed out once, committing or cancelling ends the preview
+    pub fn take(&mut self, preview_id: u32) -> Option<PendingImport> {
+        self.evict_expired();
+        self.previews
+            .remove(&preview_id)
+            .map(|(pending, _)| pending)
+    }
+
+    fn evict_expired(&mut self) {
+        self.previews
+            .retain(|_, (_, opened)| opened.elapsed() < PREVIEW_TTL);
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewItem {
+    // Position in the parsed file, used to pick the item when committing
+    pub index: usize,
+    #[serde(flatten)]
+    pub item: ParsedBookmarkWithTags,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewDuplicate {
+    pub index: usize,
+    #[serde(flatten)]
+    pub item: ParsedBookmarkWithTags,
+    // The stored bookmark with the same canonical link
+    pub existing: Option<BookmarkWithTags>,
+    // An earlier item of the same file with the same canonical link
+    pub earlier_index: Option<usize>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportPreview {
+    pub preview_id: u32,
+    pub new_items: Vec<PreviewItem>,
+    pub duplicates: Vec<PreviewDuplicate>,
+    pub failed: Vec<ParseFailBookmark>,
+}
+
+// An item to commit, with the duplicate policy to use for it instead of the default one
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportChoice {
+    pub index: usize,
+    pub policy: Option<DuplicatePolicy>,
+}
+
+// Sort parsed items into new ones and duplicates, of stored bookmarks or of earlier items
+pub fn build_preview(
+    conn: &mut DbConnection,
+    preview_id: u32,
+    parsed: &[ParsedBookmarkWithTags],
+    failed: Vec<ParseFailBookmark>,
+    tracking_params: &[String],
+) -> QueryResult<ImportPreview> {
+    use crate::schema::bookmarks_table;
+
+    let canonical_links: Vec<String> = parsed
+        .iter()
+        .map(|item| canonicalize_url(&item.bookmark.link, tracking_params))
+        .collect();
+
+    // Earliest stored bookmark per canonical link, like insert_bookmark picks it
+    let mut stored: HashMap<String, Bookmark> = HashMap::new();
+    for chunk in canonical_links.chunks(LOOKUP_CHUNK_SIZE) {
+        let rows: Vec<(String, Bookmark)> = bookmarks_table::table
+            .filter(bookmarks_table::canonical_link.eq_any(chunk))
+            .order(bookmarks_table::id.desc())
+            .select((bookmarks_table::canonical_link, Bookmark::as_select()))
+            .load(conn)?;
+        stored.extend(rows);
+    }
+
+    let stored_bookmarks: Vec<Bookmark> = stored.values().cloned().collect();
+    let mut existing_bookmarks: HashMap<i32, BookmarkWithTags> = HashMap::new();
+    for chunk in stored_bookmarks.chunks(LOOKUP_CHUNK_SIZE) {
+        existing_bookmarks.extend(
+            attach_tags(conn, chunk.to_vec())
+                .into_iter()
+                .map(|bookmark| (bookmark.bookmark.id, bookmark)),
+        );
+    }
+
+    let mut first_index: HashMap<&str, usize> = HashMap::new();
+    let mut new_items: Vec<PreviewItem> = Vec::new();
+    let mut duplicates: Vec<PreviewDuplicate> = Vec::new();
+
+    for (index, (item, canonical_link)) in parsed.iter().zip(&canonical_links).enumerate() {
+        let existing = stored
+            .get(canonical_link)
+            .and_then(|bookmark| existing_bookmarks.get(&bookmark.id))
+            .cloned();
+        let earlier_index = first_index.get(canonical_link.as_str()).copied();
+        first_index.entry(canonical_link).or_insert(index);
+
+        if existing.is_none() && earlier_index.is_none() {
+            new_items.push(PreviewItem {
+                index,
+                item: item.clone(),
+            });
+        } else {
+            duplicates.push(PreviewDuplicate {
+                index,
+                item: item.clone(),
+                existing,
+                earlier_index,
+            });
+        }
+    }
+
+    Ok(ImportPreview {
+        preview_id,
+        new_items,
+        duplicates,
+        failed,
+    })
+}
@@ -7,18 +7,37 @@ use time::OffsetDateTime;
 use url::Url;
 use utils::{capture_ctrl_c, watch_config};
 
+mod bookmark_stream;
+mod chromium_parser;
 mod commands;
 mod custom_parsers;
 mod database_cmds;
+mod duplicates;
+mod embedded_python;
+mod exporter_errors;
+mod exporters;
+mod firefox_places_parser;
+mod import_batches;
+mod import_jobs;
+mod import_preview;
 mod logger;
+mod lua_parser;
 mod models;
+mod netscape_parser;
+mod pagination;
 mod parser_errors;
+mod query_parser;
 mod runtime;
 mod schema;
+mod search;
+mod service_parsers;
 mod setup;
 mod structs;
+mod tag_tree;
 mod tray;
+mod url_canonical;
 mod utils;
+mod wasm_parser;
 
 #[cfg(target_os = "macos")]
 mod dock;
@@ -109,18 +128,43 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .invoke_handler(tauri::generate_handler![
             commands::get_db_path,
+            commands::get_duplicate_policy,
+            commands::set_duplicate_policy,
             commands::create_db,
             commands::open_db,
             commands::get_bookmarks,
+            commands::stream_bookmarks,
+            commands::ack_bookmark_stream,
+            commands::cancel_bookmark_stream,
             commands::get_all_tags,
+            commands::get_tags_with_counts,
+            commands::get_tag_tree,
+            commands::find_duplicates,
             commands::import_bookmarks,
+            commands::get_import_job,
+            commands::list_import_jobs,
+            commands::cancel_import_job,
+            commands::preview_import,
+            commands::commit_import,
+            commands::cancel_import,
+            commands::get_imports,
+            database_cmds::import_rollback,
+            commands::export_bookmarks,
+            commands::list_exporters,
             commands::list_all_custom_parsers,
             commands::list_supported_parsers,
             commands::add_custom_parser,
+            commands::list_all_custom_exporters,
+            commands::add_custom_exporter,
             database_cmds::bookmark_insert,
             database_cmds::bookmark_update,
             database_cmds::bookmark_delete,
             database_cmds::tags_update,
+            database_cmds::tag_update_metadata,
+            database_cmds::tag_rename,
+            database_cmds::tags_merge,
+            database_cmds::tag_delete,
+            database_cmds::merge_duplicates,
             database_cmds::batch_delete,
         ])
         .build(tauri::generate_context!())
@@ -0,0 +1,392 @@
+use std::cell::{Cell, RefCell};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use mlua::{HookTriggers, Lua, LuaOptions, LuaSerdeExt, SerializeOptions, StdLib, Value, Variadic};
+use serde::Deserialize;
+use time::OffsetDateTime;
+
+use crate::custom_parsers::{CancelFlag, ParseProgress, Parser, ParserSuccess};
+use crate::models::BookmarkNew;
+use crate::netscape_parser::{attribute, element_text, parse_add_date, tokenize, Token};
+use crate::parser_errors::ParserError;
+use crate::structs::{ParseFailBookmark, ParsedBookmarkWithTags, ParserConfig, ScriptOptions};
+
+const DEFAULT_MEMORY_LIMIT_MB: usize = 256;
+const DEFAULT_INSTRUCTION_LIMIT: u64 = 1_000_000_000;
+const DEFAULT_TIMEOUT_SECS: u64 = 300;
+// The limits are checked every this many instructions, checking after each one is slow
+const HOOK_INTERVAL: u32 = 10_000;
+// The tokens of an HTML document take a few times the size of its text
+const HTML_TOKENS_OVERHEAD: usize = 4;
+
+// Runs Lua scripts in an embedded VM, no interpreter has to be installed. Scripts only get
+// the table, string, math and utf8 libraries, everything else goes through `pcpocket`.
+pub struct LuaParser {
+    pub name: String,
+    r#type: String,
+    path: String,
+    supported_formats: Vec<String>,
+    options: ScriptOptions,
+}
+
+// A bookmark as scripts emit it, only the link is required
+#[derive(Deserialize)]
+struct LuaBookmark {
+    title: Option<String>,
+    link: String,
+    icon_link: Option<String>,
+    created_at: Option<i64>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+impl LuaParser {
+    pub fn new(parser_info: &ParserConfig) -> Result<Self, ParserError> {
+        let source = fs::read_to_string(&parser_info.path).map_err(|e| {
+            ParserError::FileReadError(format!("Lua script not found: {}: {}", parser_info.path, e))
+        })?;
+
+        // Catch syntax errors when the parser is added instead of on the first import
+        Lua::new_with(StdLib::NONE, LuaOptions::new())?
+            .load(&source)
+            .set_name(&parser_info.path)
+            .into_function()?;
+
+        Ok(Self {
+            name: parser_info.name.to_string(),
+            r#type: parser_info.r#type.to_string(),
+            path: parser_info.path.to_string(),
+            supported_formats: parser_info.supported_formats.to_vec(),
+            options: parser_info.script.clone(),
+        })
+    }
+
+    // In bytes, it also bounds what the app itself allocates for the script
+    fn memory_limit(&self) -> usize {
+        self.options
+            .memory_limit_mb
+            .unwrap_or(DEFAULT_MEMORY_LIMIT_MB)
+            * 1024
+            * 1024
+    }
+
+    fn timeout_secs(&self) -> u64 {
+        self.options.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS)
+    }
+
+    // A fresh VM for every parse, with the limits set and no access to files or processes
+    fn sandbox(&self, input_path: &str, cancel: &CancelFlag) -> Result<Lua, ParserError> {
+        let lua = Lua::new_with(
+            StdLib::TABLE | StdLib::STRING | StdLib::MATH | StdLib::UTF8,
+            LuaOptions::new(),
+        )?;
+        let globals = lua.globals();
+        for name in ["dofile", "loadfile", "load"] {
+            globals.raw_remove(name)?;
+        }
+
+        let memory_limit = self.memory_limit();
+        lua.set_memory_limit(memory_limit)?;
+
+        let instruction_limit = self
+            .options
+            .instruction_limit
+            .unwrap_or(DEFAULT_INSTRUCTION_LIMIT);
+        let timeout = self.timeout_secs();
+        let started = Instant::now();
+        let executed = Cell::new(0u64);
+        let cancel = cancel.clone();
+        lua.set_hook(
+            HookTriggers::new().every_nth_instruction(HOOK_INTERVAL),
+            move |_, _| {
+                if cancel.is_cancelled() {
+                    return Err(mlua::Error::runtime("Lua script was cancelled"));
+                }
+                executed.set(executed.get() + HOOK_INTERVAL as u64);
+                if executed.get() > instruction_limit {
+                    return Err(mlua::Error::runtime(format!(
+                        "Instruction limit of {} reached",
+                        instruction_limit
+                    )));
+                }
+                if started.elapsed() > Duration::from_secs(timeout) {
+                    return Err(mlua::Error::runtime(format!(
+                        "Lua script timed out after {} seconds",
+                        timeout
+                    )));
+                }
+                Ok(())
+            },
+        );
+
+        let parser_name = self.name.clone();
+        globals.set(
+            "print",
+            lua.create_function(move |_, values: Variadic<Value>| {
+                let line = values
+                    .iter()
+                    .map(|value| value.to_string())
+                    .collect::<mlua::Result<Vec<String>>>()?
+                    .join("\t");
+                log::info!("Parser {}: {}", parser_name, line);
+                Ok(())
+            })?,
+        )?;
+
+        let pcpocket = lua.create_table()?;
+        pcpocket.set("input_path", input_path)?;
+
+        // Only the file being imported can be read. It is read outside the VM's memory
+        // limit, so files that couldn't fit in it aren't read at all.
+        let input = input_path.to_string();
+        pcpocket.set(
+            "read_input",
+            lua.create_function(move |_, ()| {
+                let size = fs::metadata(&input).map_err(mlua::Error::external)?.len();
+                if size > memory_limit as u64 {
+                    return Err(over_memory_limit("Input file", memory_limit));
+                }
+                fs::read_to_string(&input).map_err(mlua::Error::external)
+            })?,
+        )?;
+
+        pcpocket.set(
+            "json_decode",
+            lua.create_function(|lua, content: String| {
+                let value: serde_json::Value =
+                    serde_json::from_str(&content).map_err(mlua::Error::external)?;
+                // null becomes nil instead of a sentinel value
+                lua.to_value_with(
+                    &value,
+                    SerializeOptions::new()
+                        .serialize_none_to_null(false)
+                        .serialize_unit_to_null(false),
+                )
+            })?,
+        )?;
+
+        // Rows become tables keyed by the header of their column
+        pcpocket.set(
+            "csv_parse",
+            lua.create_function(|lua, content: mlua::String| {
+                let mut reader = csv::ReaderBuilder::new()
+                    .flexible(true)
+                    .from_reader(content.as_bytes());
+                let headers = reader.headers().map_err(mlua::Error::external)?.clone();
+
+                let rows = lua.create_table()?;
+                for record in reader.records() {
+                    let record = record.map_err(mlua::Error::external)?;
+                    let row = lua.create_table()?;
+                    for (header, field) in headers.iter().zip(record.iter()) {
+                        row.set(header, field)?;
+                    }
+                    rows.push(row)?;
+                }
+                Ok(rows)
+            })?,
+        )?;
+
+        // Every <a> with a link and the attributes Netscape files use, ready to be emitted
+        pcpocket.set(
+            "html_links",
+            lua.create_function(move |lua, content: mlua::String| {
+                if content.as_bytes().len() * HTML_TOKENS_OVERHEAD > memory_limit {
+                    return Err(over_memory_limit("HTML document", memory_limit));
+                }
+                let content = content.to_string_lossy();
+                let tokens = tokenize(&content);
+                let links = lua.create_table()?;
+
+                for (position, token) in tokens.iter().enumerate() {
+                    let Token::Open {
+                        name, attributes, ..
+                    } = token
+                    else {
+                        continue;
+                    };
+                    if name != "a" {
+                        continue;
+                    }
+                    let Some(href) = attribute(attributes, "href") else {
+                        continue;
+                    };
+
+                    let link = lua.create_table()?;
+                    link.set("link", href)?;
+                    link.set(
+                        "title",
+                        element_text(&tokens, position + 1, "a").map(|(title, _)| title),
+                    )?;
+                    link.set(
+                        "created_at",
+                        attribute(attributes, "add_date").and_then(|date| parse_add_date(&date)),
+                    )?;
+                    link.set(
+                        "icon_link",
+                        attribute(attributes, "icon_uri").or_else(|| attribute(attributes, "icon")),
+                    )?;
+                    let tags: Vec<String> = attribute(attributes, "tags")
+                        .unwrap_or_default()
+                        .split(',')
+                        .map(|tag| tag.trim().to_string())
+                        .filter(|tag| !tag.is_empty())
+                        .collect();
+                    link.set("tags", tags)?;
+                    links.push(link)?;
+                }
+                Ok(links)
+            })?,
+        )?;
+
+        globals.set("pcpocket", pcpocket)?;
+        drop(globals);
+
+        Ok(lua)
+    }
+}
+
+fn over_memory_limit(what: &str, memory_limit: usize) -> mlua::Error {
+    mlua::Error::runtime(format!(
+        "{} is larger than the memory limit of {} MB",
+        what,
+        memory_limit / 1024 / 1024
+    ))
+}
+
+impl Parser for LuaParser {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn supported_formats(&self) -> Vec<&str> {
+        self.supported_formats.iter().map(|s| s.as_str()).collect()
+    }
+
+    fn parse(&self, input_path: &str) -> Result<ParserSuccess, ParserError> {
+        self.parse_with_progress(input_path, &mut |_| {}, &CancelFlag::default())
+    }
+
+    // The script reports its results with pcpocket.emit, pcpocket.fail and pcpocket.progress.
+    // What it reports is kept outside the VM, so it counts against the memory limit as well.
+    // Cancelling and the timeout are checked by the instruction hook, a call into the string
+    // library that runs long only ends when it returns.
+    fn parse_with_progress(
+        &self,
+        input_path: &str,
+        progress: &mut dyn FnMut(&ParseProgress),
+        cancel: &CancelFlag,
+    ) -> Result<ParserSuccess, ParserError> {
+        if !Path::new(input_path).exists() {
+            return Err(ParserError::FileReadError(format!(
+                "File not found: {}",
+                input_path
+            )));
+        }
+        let source = fs::read_to_string(&self.path).map_err(|e| {
+            ParserError::FileReadError(format!("Lua script not found: {}: {}", self.path, e))
+        })?;
+
+        let lua = self.sandbox(input_path, cancel)?;
+        let memory_limit = self.memory_limit();
+        let successful: RefCell<Vec<ParsedBookmarkWithTags>> = RefCell::new(Vec::new());
+        let failed: RefCell<Vec<ParseFailBookmark>> = RefCell::new(Vec::new());
+        let entries = || (successful.borrow().len() + failed.borrow().len()) as i32;
+        let output_size = Cell::new(0usize);
+        let add_output = |size: usize| {
+            output_size.set(output_size.get() + size);
+            if output_size.get() > memory_limit {
+                return Err(over_memory_limit("Parser output", memory_limit));
+            }
+            Ok(())
+        };
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+
+        lua.scope(|scope| {
+            let pcpocket: mlua::Table = lua.globals().get("pcpocket")?;
+
+            // A bookmark that doesn't fit is reported as failed, the script carries on
+            pcpocket.set(
+                "emit",
+                scope.create_function(|lua, value: Value| {
+                    match lua.from_value::<LuaBookmark>(value.clone()) {
+                        Ok(bookmark) => {
+                            add_output(
+                                std::mem::size_of::<ParsedBookmarkWithTags>()
+                                    + bookmark.link.len()
+                                    + bookmark.title.as_ref().map_or(0, String::len)
+                                    + bookmark.icon_link.as_ref().map_or(0, String::len)
+                                    + bookmark.tags.iter().map(String::len).sum::<usize>(),
+                            )?;
+                            successful.borrow_mut().push(ParsedBookmarkWithTags {
+                                bookmark: BookmarkNew {
+                                    title: bookmark.title,
+                                    link: bookmark.link,
+                                    icon_link: bookmark.icon_link,
+                                    created_at: bookmark.created_at.unwrap_or(now),
+                                },
+                                tags: bookmark.tags,
+                            })
+                        }
+                        Err(e) => {
+                            let item = lua
+                                .from_value::<serde_json::Value>(value)
+                                .map(|item| item.to_string())
+                                .unwrap_or_default();
+                            let error = format!("Invalid bookmark: {}", e);
+                            add_output(
+                                std::mem::size_of::<ParseFailBookmark>() + item.len() + error.len(),
+                            )?;
+                            let index = entries();
+                            failed
+                                .borrow_mut()
+                                .push(ParseFailBookmark::new(index, item, error));
+                        }
+                    }
+                    Ok(())
+                })?,
+            )?;
+
+            pcpocket.set(
+                "fail",
+                scope.create_function(|_, (item, error): (String, String)| {
+                    add_output(
+                        std::mem::size_of::<ParseFailBookmark>() + item.len() + error.len(),
+                    )?;
+                    let index = entries();
+                    failed
+                        .borrow_mut()
+                        .push(ParseFailBookmark::new(index, item, error));
+                    Ok(())
+                })?,
+            )?;
+
+            pcpocket.set(
+                "progress",
+                scope.create_function_mut(|_, (processed, total): (usize, Option<usize>)| {
+                    progress(&ParseProgress { processed, total });
+                    Ok(())
+                })?,
+            )?;
+
+            lua.load(&source).set_name(&self.path).exec()
+        })?;
+
+        Ok(ParserSuccess::new(
+            successful.into_inner(),
+            failed.into_inner(),
+        ))
+    }
+
+    fn info(&self) -> ParserConfig {
+        ParserConfig {
+            name: self.name.to_string(),
+            r#type: self.r#type.to_string(),
+            path: self.path.to_string(),
+            supported_formats: self.supported_formats.clone(),
+            script: self.options.clone(),
+        }
+    }
+}
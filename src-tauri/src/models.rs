@@ -21,11 +21,39 @@ pub struct BookmarkNew {
     pub created_at: i64,
 }
 
+#[derive(Queryable, Selectable, Identifiable, Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[diesel(table_name = crate::schema::tags)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Tag {
+    pub id: i32,
+    pub name: String,
+    pub colour: Option<String>,
+    pub description: Option<String>,
+    pub created_at: i64,
+    pub parent_id: Option<i32>,
+}
+
+#[derive(Insertable, Deserialize, Debug)]
+#[diesel(table_name = crate::schema::tags)]
+pub struct TagNew {
+    pub name: String,
+    pub parent_id: Option<i32>,
+}
+
+#[derive(AsChangeset, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = crate::schema::tags)]
+#[diesel(treat_none_as_null = true)]
+pub struct TagMetadata {
+    pub colour: Option<String>,
+    pub description: Option<String>,
+}
+
 #[derive(
     Queryable,
     Selectable,
     Identifiable,
     Associations,
+    Insertable,
     Serialize,
     Deserialize,
     Debug,
@@ -33,17 +61,48 @@ pub struct BookmarkNew {
     Clone,
 )]
 #[diesel(belongs_to(Bookmark))]
-#[diesel(table_name = crate::schema::tags_table)]
+#[diesel(belongs_to(Tag))]
+#[diesel(table_name = crate::schema::bookmark_tags)]
+#[diesel(primary_key(bookmark_id, tag_id))]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
-pub struct Tag {
-    pub id: i32,
+pub struct BookmarkTag {
     pub bookmark_id: i32,
-    pub tag_name: String,
+    pub tag_id: i32,
 }
 
-#[derive(Insertable, Deserialize, Debug)]
-#[diesel(table_name = crate::schema::tags_table)]
-pub struct TagNew {
+#[derive(Queryable, Selectable, Identifiable, Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[diesel(table_name = crate::schema::imports)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Import {
+    pub id: i32,
+    pub source_file: String,
+    pub parser_name: String,
+    pub created_at: i64,
+    pub inserted: i32,
+    pub skipped: i32,
+    pub merged: i32,
+    pub updated: i32,
+    pub failed: i32,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::imports)]
+pub struct ImportNew<'a> {
+    pub source_file: &'a str,
+    pub parser_name: &'a str,
+    pub failed: i32,
+}
+
+#[derive(Queryable, Selectable, Insertable, Debug, Clone)]
+#[diesel(table_name = crate::schema::import_overwrites)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct ImportOverwrite {
+    pub import_id: i32,
     pub bookmark_id: i32,
-    pub tag_name: String,
+    pub title: Option<String>,
+    pub link: String,
+    pub icon_link: Option<String>,
+    pub created_at: i64,
+    pub canonical_link: String,
+    pub tags: String,
 }
@@ -0,0 +1,474 @@
+use std::fs;
+
+use time::OffsetDateTime;
+
+use crate::custom_parsers::{Parser, ParserSuccess};
+use crate::models::BookmarkNew;
+use crate::parser_errors::ParserError;
+use crate::structs::{ParseFailBookmark, ParsedBookmarkWithTags, ParserConfig, ScriptOptions};
+use crate::tag_tree::TAG_SEPARATOR;
+
+// Pieces of a Netscape bookmark file. Tag names are lowercased,
+// attributes are kept raw and only parsed for the tags that need them.
+#[derive(Debug, PartialEq)]
+pub enum Token<'a> {
+    Open {
+        name: String,
+        attributes: &'a str,
+        raw: &'a str,
+    },
+    Close(String),
+    Text(&'a str),
+}
+
+// Split the file into tags and the text between them. Comments and declarations
+// (<!DOCTYPE ...>) are dropped, a `>` inside a quoted attribute doesn't end the tag.
+pub fn tokenize(input: &str) -> Vec<Token<'_>> {
+    let bytes = input.as_bytes();
+    let mut tokens = Vec::new();
+    let mut position = 0;
+
+    while position < bytes.len() {
+        if bytes[position] != b'<' {
+            let end = input[position..]
+                .find('<')
+                .map_or(input.len(), |offset| position + offset);
+            tokens.push(Token::Text(&input[position..end]));
+            position = end;
+            continue;
+        }
+
+        if input[position..].starts_with("<!--") {
+            position = input[position..]
+                .find("-->")
+                .map_or(input.len(), |offset| position + offset + 3);
+            continue;
+        }
+
+        // Find the end of the tag, skipping over quoted attribute values
+        let mut end = position + 1;
+        let mut quote: Option<u8> = None;
+        while end < bytes.len() {
+            match (quote, bytes[end]) {
+                (None, b'>') => break,
+                (None, b'"') | (None, b'\'') => quote = Some(bytes[end]),
+                (Some(open), c) if c == open => quote = None,
+                _ => {}
+            }
+            end += 1;
+        }
+
+        let inner = &input[position + 1..end.min(input.len())];
+        let raw = &input[position..(end + 1).min(input.len())];
+        position = (end + 1).min(input.len());
+
+        if inner.starts_with('!') || inner.starts_with('?') {
+            continue;
+        }
+        if let Some(name) = inner.strip_prefix('/') {
+            tokens.push(Token::Close(name.trim().to_lowercase()));
+            continue;
+        }
+
+        let name_end = inner
+            .find(|c: char| c.is_whitespace() || c == '/')
+            .unwrap_or(inner.len());
+        tokens.push(Token::Open {
+            name: inner[..name_end].to_lowercase(),
+            attributes: &inner[name_end..],
+            raw,
+        });
+    }
+
+    tokens
+}
+
+// Value of an attribute, matched case-insensitively. Quoted and bare values are both accepted.
+pub fn attribute(attributes: &str, wanted: &str) -> Option<String> {
+    let mut rest = attributes;
+
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+        if rest.is_empty() {
+            return None;
+        }
+
+        let name_end = rest
+            .find(|c: char| c.is_whitespace() || c == '=')
+            .unwrap_or(rest.len());
+        let name = &rest[..name_end];
+        rest = rest[name_end..].trim_start();
+
+        let value = match rest.strip_prefix('=') {
+            Some(after_equals) => {
+                let after_equals = after_equals.trim_start();
+                match after_equals.chars().next() {
+                    Some(quote) if quote == '"' || quote == '\'' => {
+                        let value_end = after_equals[1..]
+                            .find(quote)
+                            .map_or(after_equals.len(), |offset| offset + 1);
+                        rest = after_equals.get(value_end + 1..).unwrap_or("");
+                        &after_equals[1..value_end]
+                    }
+                    _ => {
+                        let value_end = after_equals
+                            .find(char::is_whitespace)
+                            .unwrap_or(after_equals.len());
+                        rest = &after_equals[value_end..];
+                        &after_equals[..value_end]
+                    }
+                }
+            }
+            None => "",
+        };
+
+        if name.eq_ignore_ascii_case(wanted) {
+            return Some(decode_entities(value));
+        }
+    }
+}
+
+// Replace the HTML character references browsers use when exporting
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let replacement = rest.find(';').and_then(|end| {
+            let entity = &rest[1..end];
+            let character = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some('\u{a0}'),
+                _ => entity
+                    .strip_prefix("#x")
+                    .or_else(|| entity.strip_prefix("#X"))
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                    .and_then(char::from_u32),
+            };
+            character.map(|character| (character, end))
+        });
+
+        match replacement {
+            Some((character, end)) => {
+                decoded.push(character);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    decoded.push_str(rest);
+    decoded
+}
+
+// Text up to the closing tag of `name`. Returns None when the element is never closed,
+// i.e. another element starts first or the file ends.
+pub fn element_text<'a>(tokens: &[Token<'a>], start: usize, name: &str) -> Option<(String, usize)> {
+    let mut text = String::new();
+
+    for (index, token) in tokens.iter().enumerate().skip(start) {
+        match token {
+            Token::Text(content) => text.push_str(content),
+            Token::Close(closed) if closed == name => {
+                return Some((decode_entities(text.trim()), index + 1));
+            }
+            Token::Open { name: opened, .. }
+                if matches!(opened.as_str(), "a" | "h3" | "dl" | "dt") =>
+            {
+                return None;
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+// ADD_DATE is in seconds, but some exporters write milliseconds or microseconds
+pub fn parse_add_date(value: &str) -> Option<i64> {
+    let timestamp: i64 = value.trim().parse().ok()?;
+    if timestamp < 0 {
+        return None;
+    }
+
+    Some(match timestamp {
+        t if t >= 100_000_000_000_000 => t / 1_000_000,
+        t if t >= 100_000_000_000 => t / 1_000,
+        t => t,
+    })
+}
+
+// Parser for the Netscape bookmark file (bookmarks.html) every browser can export.
+// Folder nesting becomes a single nested tag, the TAGS attribute adds plain tags.
+pub struct NetscapeHtmlParser {
+    name: String,
+    r#type: String,
+    path: String,
+    supported_formats: Vec<String>,
+    // Characters the TAGS attribute is split on, browsers and most services use commas
+    tag_separator: fn(char) -> bool,
+}
+
+impl NetscapeHtmlParser {
+    pub fn new() -> Self {
+        NetscapeHtmlParser {
+            name: "Default HTML".to_string(),
+            r#type: "default".to_string(),
+            path: "In app".to_string(),
+            supported_formats: vec!["html".to_string(), "htm".to_string()],
+            tag_separator: |c| c == ',',
+        }
+    }
+
+    pub fn with_tag_separator(mut self, tag_separator: fn(char) -> bool) -> Self {
+        self.tag_separator = tag_separator;
+        self
+    }
+
+    pub fn parse_content(&self, content: &str) -> Result<ParserSuccess, ParserError> {
+        let tokens = tokenize(content);
+
+        if !tokens
+            .iter()
+            .any(|token| matches!(token, Token::Open { name, .. } if name == "dl"))
+        {
+            return Err(ParserError::InvalidFormat(
+                "Not a Netscape bookmark file, no <DL> list found".to_string(),
+            ));
+        }
+
+        let mut successful: Vec<ParsedBookmarkWithTags> = Vec::new();
+        let mut failed: Vec<ParseFailBookmark> = Vec::new();
+
+        // One entry per open <DL>, None for lists that don't belong to a named folder
+        let mut folders: Vec<Option<String>> = Vec::new();
+        let mut pending_folder: Option<String> = None;
+        let mut entry_index = 0;
+        let mut position = 0;
+
+        while position < tokens.len() {
+            match &tokens[position] {
+                Token::Open { name, .. } if name == "h3" => {
+                    match element_text(&tokens, position + 1, "h3") {
+                        Some((title, next)) => {
+                            pending_folder = Some(title).filter(|title| !title.is_empty());
+                            position = next;
+                        }
+                        None => {
+                            pending_folder = None;
+                            position += 1;
+                        }
+                    }
+                    continue;
+                }
+                Token::Open { name, .. } if name == "dl" => {
+                    folders.push(pending_folder.take());
+                }
+                Token::Close(name) if name == "dl" => {
+                    folders.pop();
+                }
+                Token::Open {
+                    name,
+                    attributes,
+                    raw,
+                } if name == "a" => {
+                    let index = entry_index;
+                    entry_index += 1;
+
+                    let (title, next) = match element_text(&tokens, position + 1, "a") {
+                        Some(element) => element,
+                        None => {
+                            failed.push(ParseFailBookmark::new(
+                                index,
+                                raw.to_string(),
+                                "Link is never closed with </A>".to_string(),
+                            ));
+                            position += 1;
+                            continue;
+                        }
+                    };
+                    position = next;
+
+                    let link = match attribute(attributes, "href") {
+                        Some(link) if !link.trim().is_empty() => link.trim().to_string(),
+                        _ => {
+                            failed.push(ParseFailBookmark::new(
+                                index,
+                                raw.to_string(),
+                                "Missing HREF".to_string(),
+                            ));
+                            continue;
+                        }
+                    };
+
+                    let created_at = match attribute(attributes, "add_date") {
+                        Some(add_date) => match parse_add_date(&add_date) {
+                            Some(created_at) => created_at,
+                            None => {
+                                failed.push(ParseFailBookmark::new(
+                                    index,
+                                    raw.to_string(),
+                                    format!("Invalid ADD_DATE: {}", add_date),
+                                ));
+                                continue;
+                            }
+                        },
+                        // Safari doesn't export dates
+                        None => OffsetDateTime::now_utc().unix_timestamp(),
+                    };
+
+                    let icon_link = attribute(attributes, "icon_uri")
+                        .or_else(|| attribute(attributes, "icon"))
+                        .filter(|icon| !icon.is_empty());
+
+                    let mut tags: Vec<String> = Vec::new();
+                    let folder_path: Vec<&str> =
+                        folders.iter().flatten().map(|f| f.as_str()).collect();
+                    if !folder_path.is_empty() {
+                        tags.push(folder_path.join(&TAG_SEPARATOR.to_string()));
+                    }
+                    // PcPocket's own exports repeat the folder's tag in TAGS
+                    if let Some(tag_list) = attribute(attributes, "tags") {
+                        for tag in tag_list.split(self.tag_separator).map(|tag| tag.trim()) {
+                            if !tag.is_empty() && !tags.iter().any(|known| known == tag) {
+                                tags.push(tag.to_string());
+                            }
+                        }
+                    }
+
+                    successful.push(ParsedBookmarkWithTags {
+                        bookmark: BookmarkNew {
+                            title: Some(title),
+                            link,
+                            icon_link,
+                            created_at,
+                        },
+                        tags,
+                    });
+                    continue;
+                }
+                _ => {}
+            }
+
+            position += 1;
+        }
+
+        Ok(ParserSuccess::new(successful, failed))
+    }
+}
+
+impl Parser for NetscapeHtmlParser {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn parse(&self, input_path: &str) -> Result<ParserSuccess, ParserError> {
+        let content = fs::read_to_string(input_path)
+            .map_err(|e| ParserError::FileReadError(format!("Error reading file: {}", e)))?;
+
+        self.parse_content(&content)
+    }
+
+    fn supported_formats(&self) -> Vec<&str> {
+        self.supported_formats.iter().map(|s| s.as_str()).collect()
+    }
+
+    fn info(&self) -> ParserConfig {
+        ParserConfig {
+            name: self.name.to_string(),
+            r#type: self.r#type.to_string(),
+            path: self.path.to_string(),
+            supported_formats: self.supported_formats.clone(),
+            script: ScriptOptions::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOOKMARKS_HTML: &str = r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<META HTTP-EQUIV="Content-Type" CONTENT="text/html; charset=UTF-8">
+<TITLE>Bookmarks</TITLE>
+<DL><p>
+    <DT><H3 ADD_DATE="1700000000">Dev</H3>
+    <DL><p>
+        <DT><H3>Rust</H3>
+        <DL><p>
+            <DT><A HREF="https://www.rust-lang.org/" ADD_DATE="1700000000" TAGS="lang, Dev/Rust,lang">Rust &amp; Cargo</A>
+        </DL><p>
+    </DL><p>
+    <DT><A HREF="https://example.com" ADD_DATE="1700000000000" ICON="data:image/png;base64,AA">Example</A>
+    <DT><A ADD_DATE="1700000000">No link</A>
+    <DT><A HREF="https://example.org" ADD_DATE="soon">Bad date</A>
+</DL><p>
+"#;
+
+    #[test]
+    fn reads_links_with_folders_as_tags() {
+        let result = NetscapeHtmlParser::new()
+            .parse_content(BOOKMARKS_HTML)
+            .unwrap();
+        let successful = result.get_successful();
+        assert_eq!(successful.len(), 2);
+
+        let rust = &successful[0];
+        assert_eq!(rust.bookmark.link, "https://www.rust-lang.org/");
+        assert_eq!(rust.bookmark.title.as_deref(), Some("Rust & Cargo"));
+        assert_eq!(rust.bookmark.created_at, 1700000000);
+        assert_eq!(rust.tags, vec!["Dev/Rust", "lang"]);
+
+        let example = &successful[1];
+        assert_eq!(example.bookmark.created_at, 1700000000);
+        assert_eq!(
+            example.bookmark.icon_link.as_deref(),
+            Some("data:image/png;base64,AA")
+        );
+        assert!(example.tags.is_empty());
+    }
+
+    #[test]
+    fn reports_broken_links() {
+        let result = NetscapeHtmlParser::new()
+            .parse_content(BOOKMARKS_HTML)
+            .unwrap();
+        assert_eq!(result.get_failed().len(), 2);
+    }
+
+    #[test]
+    fn splits_tags_on_the_configured_separator() {
+        let html = r#"<DL><DT><A HREF="https://a.com" ADD_DATE="1" TAGS="rust web">A</A></DL>"#;
+        let result = NetscapeHtmlParser::new()
+            .with_tag_separator(char::is_whitespace)
+            .parse_content(html)
+            .unwrap();
+        assert_eq!(result.get_successful()[0].tags, vec!["rust", "web"]);
+    }
+
+    #[test]
+    fn rejects_files_without_a_list() {
+        assert!(NetscapeHtmlParser::new()
+            .parse_content("<html><a href=\"https://a.com\">A</a></html>")
+            .is_err());
+    }
+
+    #[test]
+    fn scales_add_dates_to_seconds() {
+        assert_eq!(parse_add_date("1700000000"), Some(1700000000));
+        assert_eq!(parse_add_date("1700000000000"), Some(1700000000));
+        assert_eq!(parse_add_date("1700000000000000"), Some(1700000000));
+        assert_eq!(parse_add_date("soon"), None);
+    }
+}
@@ -0,0 +1,283 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use diesel::expression::expression_types::NotSelectable;
+use diesel::expression::BoxableExpression;
+use diesel::prelude::*;
+use diesel::sql_types::{Bool, Nullable, Text};
+use diesel::sqlite::Sqlite;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::models::Bookmark;
+use crate::query_parser::BookmarksQuery;
+use crate::schema::bookmarks_table;
+
+type KeysetFilter = Box<dyn BoxableExpression<bookmarks_table::table, Sqlite, SqlType = Bool>>;
+type Ordering = Box<dyn BoxableExpression<bookmarks_table::table, Sqlite, SqlType = NotSelectable>>;
+
+diesel::define_sql_function!(fn ifnull(x: Nullable<Text>, y: Text) -> Text);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortColumn {
+    Title,
+    Link,
+    CreatedAt,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct SortKey {
+    pub column: SortColumn,
+    pub desc: bool,
+}
+
+impl SortKey {
+    // Unknown columns are ignored, the same way the offset paging sort does
+    pub fn from_id(id: &str, desc: bool) -> Option<Self> {
+        let column = match id {
+            "title" => SortColumn::Title,
+            "link" => SortColumn::Link,
+            "created_at" => SortColumn::CreatedAt,
+            _ => return None,
+        };
+        Some(SortKey { column, desc })
+    }
+
+    fn ordering(&self) -> Ordering {
+        use crate::schema::bookmarks_table::dsl::*;
+
+        // Untitled bookmarks sort as an empty title so they can be compared in a cursor
+        match (self.column, self.desc) {
+            (SortColumn::Title, false) => Box::new(ifnull(title, "").asc()),
+            (SortColumn::Title, true) => Box::new(ifnull(title, "").desc()),
+            (SortColumn::Link, false) => Box::new(link.asc()),
+            (SortColumn::Link, true) => Box::new(link.desc()),
+            (SortColumn::CreatedAt, false) => Box::new(created_at.asc()),
+            (SortColumn::CreatedAt, true) => Box::new(created_at.desc()),
+        }
+    }
+
+    fn value_of(&self, bookmark: &Bookmark) -> Value {
+        match self.column {
+            SortColumn::Title => Value::from(bookmark.title.clone().unwrap_or_default()),
+            SortColumn::Link => Value::from(bookmark.link.clone()),
+            SortColumn::CreatedAt => Value::from(bookmark.created_at),
+        }
+    }
+
+    // Rows that come after `value` in this key's direction
+    fn after(&self, value: &Value) -> Option<KeysetFilter> {
+        use crate::schema::bookmarks_table::dsl::*;
+
+        let filter: KeysetFilter = match (self.column, self.desc) {
+            (SortColumn::Title, false) => {
+                Box::new(ifnull(title, "").gt(value.as_str()?.to_string()))
+            }
+            (SortColumn::Title, true) => {
+                Box::new(ifnull(title, "").lt(value.as_str()?.to_string()))
+            }
+            (SortColumn::Link, false) => Box::new(link.gt(value.as_str()?.to_string())),
+            (SortColumn::Link, true) => Box::new(link.lt(value.as_str()?.to_string())),
+            (SortColumn::CreatedAt, false) => Box::new(created_at.gt(value.as_i64()?)),
+            (SortColumn::CreatedAt, true) => Box::new(created_at.lt(value.as_i64()?)),
+        };
+        Some(filter)
+    }
+
+    fn equals(&self, value: &Value) -> Option<KeysetFilter> {
+        use crate::schema::bookmarks_table::dsl::*;
+
+        let filter: KeysetFilter = match self.column {
+            SortColumn::Title => Box::new(ifnull(title, "").eq(value.as_str()?.to_string())),
+            SortColumn::Link => Box::new(link.eq(value.as_str()?.to_string())),
+            SortColumn::CreatedAt => Box::new(created_at.eq(value.as_i64()?)),
+        };
+        Some(filter)
+    }
+}
+
+// Position of the last row of a page: its sort key values plus its id as the tie breaker.
+// Sent to the frontend as an opaque base64 string.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Cursor {
+    sort: Vec<SortKey>,
+    values: Vec<Value>,
+    id: i32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CursorError {
+    Malformed,
+    SortMismatch,
+}
+
+impl std::fmt::Display for CursorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CursorError::Malformed => write!(f, "Malformed cursor"),
+            CursorError::SortMismatch => {
+                write!(f, "Cursor was created for a different sort order")
+            }
+        }
+    }
+}
+
+impl Cursor {
+    pub fn after(bookmark: &Bookmark, sort_keys: &[SortKey]) -> Self {
+        Cursor {
+            sort: sort_keys.to_vec(),
+            values: sort_keys.iter().map(|key| key.value_of(bookmark)).collect(),
+            id: bookmark.id,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    pub fn decode(encoded: &str, sort_keys: &[SortKey]) -> Result<Self, CursorError> {
+        let bytes = URL_SAFE_NO_PAD
+            .decode(encoded)
+            .map_err(|_| CursorError::Malformed)?;
+        let cursor: Cursor = serde_json::from_slice(&bytes).map_err(|_| CursorError::Malformed)?;
+
+        if cursor.values.len() != cursor.sort.len() {
+            return Err(CursorError::Malformed);
+        }
+        if cursor.sort != sort_keys {
+            return Err(CursorError::SortMismatch);
+        }
+        Ok(cursor)
+    }
+
+    // (k1 > v1) OR (k1 = v1 AND k2 > v2) OR ... OR (k1 = v1 AND ... AND id > last_id)
+    fn keyset_filter(&self) -> Result<KeysetFilter, CursorError> {
+        use crate::schema::bookmarks_table::dsl::id;
+
+        let mut keyset: Option<KeysetFilter> = None;
+
+        for i in 0..=self.sort.len() {
+            let after: KeysetFilter = match self.sort.get(i) {
+                Some(key) => key.after(&self.values[i]).ok_or(CursorError::Malformed)?,
+                None => Box::new(id.gt(self.id)),
+            };
+            let branch: KeysetFilter = match self.equal_prefix(i)? {
+                Some(equal) => Box::new(equal.and(after)),
+                None => after,
+            };
+            keyset = Some(match keyset {
+                Some(keyset) => Box::new(keyset.or(branch)),
+                None => branch,
+            });
+        }
+
+        keyset.ok_or(CursorError::Malformed)
+    }
+
+    // Equality on the first `len` sort keys
+    fn equal_prefix(&self, len: usize) -> Result<Option<KeysetFilter>, CursorError> {
+        let mut prefix: Option<KeysetFilter> = None;
+        for (key, value) in self.sort.iter().zip(&self.values).take(len) {
+            let equals = key.equals(value).ok_or(CursorError::Malformed)?;
+            prefix = Some(match prefix {
+                Some(prefix) => Box::new(prefix.and(equals)),
+                None => equals,
+            });
+        }
+        Ok(prefix)
+    }
+}
+
+// Order by the sort keys with the id as the final tie breaker, then skip past the cursor
+pub fn apply_keyset<'a>(
+    mut query: BookmarksQuery<'a>,
+    sort_keys: &[SortKey],
+    cursor: Option<&Cursor>,
+) -> Result<BookmarksQuery<'a>, CursorError> {
+    use crate::schema::bookmarks_table::dsl::id;
+
+    for (i, key) in sort_keys.iter().enumerate() {
+        if i == 0 {
+            query = query.order(key.ordering());
+        } else {
+            query = query.then_order_by(key.ordering());
+        }
+    }
+    if sort_keys.is_empty() {
+        query = query.order(id.asc());
+    } else {
+        query = query.then_order_by(id.asc());
+    }
+
+    if let Some(cursor) = cursor {
+        query = query.filter(cursor.keyset_filter()?);
+    }
+
+    Ok(query)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bookmark() -> Bookmark {
+        Bookmark {
+            id: 7,
+            title: None,
+            link: "https://example.com".to_string(),
+            icon_link: None,
+            created_at: 1700000000,
+        }
+    }
+
+    fn sort_keys() -> Vec<SortKey> {
+        vec![
+            SortKey::from_id("title", false).unwrap(),
+            SortKey::from_id("created_at", true).unwrap(),
+        ]
+    }
+
+    #[test]
+    fn cursor_round_trips() {
+        let cursor = Cursor::after(&bookmark(), &sort_keys());
+        assert_eq!(
+            cursor.values,
+            vec![Value::from(""), Value::from(1700000000)]
+        );
+        assert_eq!(Cursor::decode(&cursor.encode(), &sort_keys()), Ok(cursor));
+    }
+
+    #[test]
+    fn cursor_of_another_sort_is_rejected() {
+        let encoded = Cursor::after(&bookmark(), &sort_keys()).encode();
+        let other_sort = vec![SortKey::from_id("title", true).unwrap()];
+        assert_eq!(
+            Cursor::decode(&encoded, &other_sort),
+            Err(CursorError::SortMismatch)
+        );
+    }
+
+    #[test]
+    fn malformed_cursors_are_rejected() {
+        assert_eq!(
+            Cursor::decode("not base64!", &sort_keys()),
+            Err(CursorError::Malformed)
+        );
+        assert_eq!(
+            Cursor::decode(&URL_SAFE_NO_PAD.encode("{}"), &sort_keys()),
+            Err(CursorError::Malformed)
+        );
+
+        // One value for two sort keys
+        let short = r#"{"sort":[{"column":"title","desc":false},{"column":"created_at","desc":true}],"values":[""],"id":7}"#;
+        assert_eq!(
+            Cursor::decode(&URL_SAFE_NO_PAD.encode(short), &sort_keys()),
+            Err(CursorError::Malformed)
+        );
+    }
+
+    #[test]
+    fn unknown_sort_columns_are_ignored() {
+        assert_eq!(SortKey::from_id("icon_link", false), None);
+    }
+}
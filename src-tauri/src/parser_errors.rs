@@ -9,7 +9,9 @@ pub enum ParserError {
     MissingField(String),
     ConfigError(String),
     PythonError(String),
+    CommandError(String),
     LuaError(String),
+    WasmError(String),
     IoError(io::Error),
 }
 
@@ -21,7 +23,9 @@ impl fmt::Display for ParserError {
             ParserError::MissingField(field) => write!(f, "Missing required field: {}", field),
             ParserError::ConfigError(msg) => write!(f, "Configuration error: {}", msg),
             ParserError::PythonError(msg) => write!(f, "Python error: {}", msg),
+            ParserError::CommandError(msg) => write!(f, "Command error: {}", msg),
             ParserError::LuaError(msg) => write!(f, "Lua error: {}", msg),
+            ParserError::WasmError(msg) => write!(f, "Wasm error: {}", msg),
             ParserError::IoError(e) => write!(f, "IO error: {}", e),
         }
     }
@@ -42,6 +46,19 @@ impl From<pyo3::PyErr> for ParserError {
     }
 }
 
+impl From<mlua::Error> for ParserError {
+    fn from(err: mlua::Error) -> Self {
+        ParserError::LuaError(err.to_string())
+    }
+}
+
+impl From<wasmtime::Error> for ParserError {
+    fn from(err: wasmtime::Error) -> Self {
+        // Alternate formatting keeps the causes, e.g. the trap behind a failed call
+        ParserError::WasmError(format!("{:#}", err))
+    }
+}
+
 impl From<String> for ParserError {
     fn from(s: String) -> Self {
         ParserError::ConfigError(s)
@@ -0,0 +1,515 @@
+use diesel::dsl::not;
+use diesel::expression::BoxableExpression;
+use diesel::prelude::*;
+use diesel::sql_types::Bool;
+use diesel::sqlite::Sqlite;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use time::{Date, Month};
+
+use crate::schema::bookmarks_table;
+use crate::search;
+use crate::tag_tree::{length, substr, subtree_ids, TAG_SEPARATOR};
+
+pub type BookmarksQuery<'a> = bookmarks_table::BoxedQuery<'a, Sqlite>;
+
+// The tags a bookmark is required to carry, joined against its own tags
+diesel::alias!(crate::schema::tags as required_tags: RequiredTags);
+
+// Search box syntax:
+//   word, word*, "exact phrase"   full-text match over titles, links and tags
+//   title:word, link:word         full-text match restricted to one column
+//   tag:name                      bookmarks tagged with exactly `name`, -tag:name excludes it
+//   site:github.com               bookmarks on a host or any of its subdomains
+//   after:2024-01-01              created on or after a date (or unix timestamp)
+//   before:2024-01-01             created before a date (or unix timestamp)
+// Any term can be negated with a leading `-`, values can be quoted: tag:"read later"
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryTerm {
+    Text(String),
+    Title(String),
+    Link(String),
+    Tag(String),
+    Site(String),
+    After(i64),
+    Before(i64),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryClause {
+    pub negated: bool,
+    pub term: QueryTerm,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedQuery {
+    pub clauses: Vec<QueryClause>,
+}
+
+// Exact-match tag filter: a bookmark must carry at least one of `any_of`,
+// every tag in `all_of` and none of the tags in `none_of`. Empty sets are ignored.
+// Nested tags count towards their ancestors.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TagExpression {
+    #[serde(default)]
+    pub any_of: Vec<String>,
+    #[serde(default)]
+    pub all_of: Vec<String>,
+    #[serde(default)]
+    pub none_of: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum QueryParseErrorKind {
+    UnterminatedQuote,
+    MissingValue,
+    InvalidDate,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryParseError {
+    pub kind: QueryParseErrorKind,
+    pub message: String,
+    // Character offset and length of the offending token in the query string
+    pub position: usize,
+    pub length: usize,
+}
+
+impl fmt::Display for QueryParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at position {})", self.message, self.position)
+    }
+}
+
+impl std::error::Error for QueryParseError {}
+
+pub fn parse_query(input: &str) -> Result<ParsedQuery, QueryParseError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut clauses = Vec::new();
+    let mut pos = 0;
+
+    while pos < chars.len() {
+        if chars[pos].is_whitespace() {
+            pos += 1;
+            continue;
+        }
+
+        let start = pos;
+        let mut negated = false;
+        if chars[pos] == '-' && pos + 1 < chars.len() && !chars[pos + 1].is_whitespace() {
+            negated = true;
+            pos += 1;
+        }
+
+        // Quoted phrase
+        if chars[pos] == '"' {
+            let (phrase, end) = read_quoted(&chars, pos, start)?;
+            pos = end;
+            let mut text = format!("\"{}\"", phrase);
+            if pos < chars.len() && chars[pos] == '*' {
+                text.push('*');
+                pos += 1;
+            }
+            clauses.push(QueryClause {
+                negated,
+                term: QueryTerm::Text(text),
+            });
+            continue;
+        }
+
+        // `key:value` operator or bare word
+        let word_start = pos;
+        while pos < chars.len()
+            && !chars[pos].is_whitespace()
+            && chars[pos] != '"'
+            && chars[pos] != ':'
+        {
+            pos += 1;
+        }
+        let key: String = chars[word_start..pos].iter().collect();
+
+        if pos < chars.len() && chars[pos] == ':' && is_operator(&key.to_lowercase()) {
+            pos += 1;
+            let value = if pos < chars.len() && chars[pos] == '"' {
+                let (value, end) = read_quoted(&chars, pos, start)?;
+                pos = end;
+                value
+            } else {
+                let value_start = pos;
+                while pos < chars.len() && !chars[pos].is_whitespace() {
+                    pos += 1;
+                }
+                chars[value_start..pos].iter().collect()
+            };
+
+            if value.trim().is_empty() {
+                return Err(QueryParseError {
+                    kind: QueryParseErrorKind::MissingValue,
+                    message: format!("`{}:` needs a value", key),
+                    position: start,
+                    length: pos - start,
+                });
+            }
+
+            let term = build_operator_term(&key.to_lowercase(), value.trim(), start, pos - start)?;
+            clauses.push(QueryClause { negated, term });
+        } else {
+            // Not an operator, so the whole token (urls included) is plain text
+            while pos < chars.len() && !chars[pos].is_whitespace() && chars[pos] != '"' {
+                pos += 1;
+            }
+            let text: String = chars[word_start..pos].iter().collect();
+            clauses.push(QueryClause {
+                negated,
+                term: QueryTerm::Text(text),
+            });
+        }
+    }
+
+    Ok(ParsedQuery { clauses })
+}
+
+fn is_operator(key: &str) -> bool {
+    matches!(key, "title" | "link" | "tag" | "site" | "after" | "before")
+}
+
+// Read a double quoted string starting at `pos`, returns its content and the position after it
+fn read_quoted(
+    chars: &[char],
+    pos: usize,
+    token_start: usize,
+) -> Result<(String, usize), QueryParseError> {
+    let mut end = pos + 1;
+    while end < chars.len() && chars[end] != '"' {
+        end += 1;
+    }
+    if end >= chars.len() {
+        return Err(QueryParseError {
+            kind: QueryParseErrorKind::UnterminatedQuote,
+            message: "Missing closing quote".to_string(),
+            position: token_start,
+            length: chars.len() - token_start,
+        });
+    }
+    Ok((chars[pos + 1..end].iter().collect(), end + 1))
+}
+
+fn build_operator_term(
+    key: &str,
+    value: &str,
+    position: usize,
+    length: usize,
+) -> Result<QueryTerm, QueryParseError> {
+    let term = match key {
+        "title" => QueryTerm::Title(value.to_string()),
+        "link" => QueryTerm::Link(value.to_string()),
+        "tag" => QueryTerm::Tag(value.to_string()),
+        "site" => QueryTerm::Site(value.trim_start_matches("www.").to_lowercase()),
+        "after" | "before" => {
+            let timestamp = parse_date(value).ok_or_else(|| QueryParseError {
+                kind: QueryParseErrorKind::InvalidDate,
+                message: format!(
+                    "`{}` is not a valid date, expected YYYY-MM-DD or a unix timestamp",
+                    value
+                ),
+                position,
+                length,
+            })?;
+            if key == "after" {
+                QueryTerm::After(timestamp)
+            } else {
+                QueryTerm::Before(timestamp)
+            }
+        }
+        _ => QueryTerm::Text(format!("{}:{}", key, value)),
+    };
+    Ok(term)
+}
+
+// Accepts YYYY-MM-DD (midnight UTC) or a raw unix timestamp
+fn parse_date(value: &str) -> Option<i64> {
+    if let Ok(timestamp) = value.parse::<i64>() {
+        return Some(timestamp);
+    }
+
+    let mut parts = value.splitn(3, '-');
+    let year = parts.next()?.parse::<i32>().ok()?;
+    let month = parts.next()?.parse::<u8>().ok()?;
+    let day = parts.next()?.parse::<u8>().ok()?;
+    let date = Date::from_calendar_date(year, Month::try_from(month).ok()?, day).ok()?;
+
+    Some(date.midnight().assume_utc().unix_timestamp())
+}
+
+// Escape LIKE wildcards so they match literally
+pub fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+impl ParsedQuery {
+    // FTS5 expression of all positive full-text terms, used for filtering and ranking
+    fn match_query(&self) -> Option<String> {
+        let terms: Vec<String> = self
+            .clauses
+            .iter()
+            .filter(|clause| !clause.negated)
+            .filter_map(|clause| text_match_query(&clause.term))
+            .collect();
+
+        if terms.is_empty() {
+            None
+        } else {
+            Some(terms.join(" "))
+        }
+    }
+
+    // Restrict a bookmarks query to the rows matching every clause
+    pub fn apply_filters<'a>(&self, mut query: BookmarksQuery<'a>) -> BookmarksQuery<'a> {
+        use crate::schema::bookmarks_table::dsl::*;
+
+        if let Some(match_query) = self.match_query() {
+            query = query.filter(search::matches(&match_query));
+        }
+
+        for clause in &self.clauses {
+            match &clause.term {
+                QueryTerm::Text(_) | QueryTerm::Title(_) | QueryTerm::Link(_) => {
+                    if clause.negated {
+                        if let Some(match_query) = text_match_query(&clause.term) {
+                            query = query.filter(not(search::matches(&match_query)));
+                        }
+                    }
+                }
+                // Collected into a single tag expression below
+                QueryTerm::Tag(_) => {}
+                QueryTerm::Site(host) => {
+                    // The host ends the link or is followed by a path or a port
+                    let host = escape_like(host);
+                    let on_site = link
+                        .like(format!("%://{}", host))
+                        .escape('\\')
+                        .or(link.like(format!("%://{}/%", host)).escape('\\'))
+                        .or(link.like(format!("%://{}:%", host)).escape('\\'))
+                        .or(link.like(format!("%://%.{}", host)).escape('\\'))
+                        .or(link.like(format!("%://%.{}/%", host)).escape('\\'))
+                        .or(link.like(format!("%://%.{}:%", host)).escape('\\'));
+                    if clause.negated {
+                        query = query.filter(not(on_site));
+                    } else {
+                        query = query.filter(on_site);
+                    }
+                }
+                QueryTerm::After(timestamp) => {
+                    if clause.negated {
+                        query = query.filter(created_at.lt(*timestamp));
+                    } else {
+                        query = query.filter(created_at.ge(*timestamp));
+                    }
+                }
+                QueryTerm::Before(timestamp) => {
+                    if clause.negated {
+                        query = query.filter(created_at.ge(*timestamp));
+                    } else {
+                        query = query.filter(created_at.lt(*timestamp));
+                    }
+                }
+            }
+        }
+
+        self.tag_expression().apply_filters(query)
+    }
+
+    // `tag:` terms must all be present, `-tag:` terms must all be absent
+    fn tag_expression(&self) -> TagExpression {
+        let mut expression = TagExpression::default();
+        for clause in &self.clauses {
+            if let QueryTerm::Tag(tag) = &clause.term {
+                if clause.negated {
+                    expression.none_of.push(tag.clone());
+                } else {
+                    expression.all_of.push(tag.clone());
+                }
+            }
+        }
+        expression
+    }
+
+    // Whether apply_rank orders the rows by relevance
+    pub fn is_ranked(&self) -> bool {
+        self.match_query().is_some()
+    }
+
+    // Order a bookmarks query by how well it matches the full-text terms
+    pub fn apply_rank<'a>(&self, query: BookmarksQuery<'a>) -> BookmarksQuery<'a> {
+        match self.match_query() {
+            Some(match_query) => query.order(search::rank(&match_query)),
+            None => query,
+        }
+    }
+}
+
+impl TagExpression {
+    // Restrict a bookmarks query to the rows whose tags satisfy the expression.
+    // A tag also matches every tag nested under it, so `dev` covers `dev/rust`.
+    // Everything is evaluated by SQLite through subqueries on bookmark_tags.
+    pub fn apply_filters<'a>(&self, mut query: BookmarksQuery<'a>) -> BookmarksQuery<'a> {
+        use crate::schema::bookmarks_table::dsl::id;
+
+        if !self.any_of.is_empty() {
+            query = query.filter(id.eq_any(tagged_bookmark_ids(&self.any_of)));
+        }
+
+        if !self.all_of.is_empty() {
+            query = query.filter(carries_all_tags(&self.all_of));
+        }
+
+        if !self.none_of.is_empty() {
+            query = query.filter(id.ne_all(tagged_bookmark_ids(&self.none_of)));
+        }
+
+        query
+    }
+}
+
+// Ids of the bookmarks carrying one of `tag_names` or a tag nested under them
+fn tagged_bookmark_ids<'a>(
+    tag_names: &[String],
+) -> crate::schema::bookmark_tags::BoxedQuery<'a, Sqlite, diesel::sql_types::Integer> {
+    use crate::schema::bookmark_tags;
+
+    bookmark_tags::table
+        .filter(bookmark_tags::tag_id.eq_any(subtree_ids(tag_names)))
+        .select(bookmark_tags::bookmark_id)
+        .into_boxed()
+}
+
+// Bookmarks carrying every one of `tag_names`, each directly or through a tag nested
+// under it. Every tag of a bookmark is paired with the required tags it falls under
+// and a single grouped subquery keeps the bookmarks for which all of them were paired.
+fn carries_all_tags(
+    tag_names: &[String],
+) -> Box<dyn BoxableExpression<bookmarks_table::table, Sqlite, SqlType = Bool>> {
+    use crate::schema::bookmarks_table::dsl::id;
+    use crate::schema::{bookmark_tags, tags};
+    use diesel::dsl::count_distinct;
+
+    let mut tag_names = tag_names.to_vec();
+    tag_names.sort();
+    tag_names.dedup();
+    let required = tag_names.len() as i64;
+
+    let required_name = required_tags.field(tags::name);
+    Box::new(
+        id.eq_any(
+            bookmark_tags::table
+                .inner_join(tags::table)
+                .inner_join(
+                    required_tags.on(tags::name.eq(required_name).or(substr(
+                        tags::name,
+                        1,
+                        length(required_name) + 1,
+                    )
+                    .eq(required_name.concat(TAG_SEPARATOR.to_string())))),
+                )
+                .filter(required_name.eq_any(tag_names))
+                .group_by(bookmark_tags::bookmark_id)
+                .having(count_distinct(required_tags.field(tags::id)).eq(required))
+                .select(bookmark_tags::bookmark_id),
+        ),
+    )
+}
+
+fn text_match_query(term: &QueryTerm) -> Option<String> {
+    match term {
+        QueryTerm::Text(text) => search::to_match_query(text),
+        QueryTerm::Title(text) => {
+            search::to_match_query(text).map(|match_query| format!("title : ({})", match_query))
+        }
+        QueryTerm::Link(text) => {
+            search::to_match_query(text).map(|match_query| format!("link : ({})", match_query))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clause(negated: bool, term: QueryTerm) -> QueryClause {
+        QueryClause { negated, term }
+    }
+
+    #[test]
+    fn parses_words_phrases_and_operators() {
+        let query =
+            parse_query(r#"rust "exact phrase" title:async -tag:"read later" site:www.GitHub.com"#)
+                .unwrap();
+        assert_eq!(
+            query.clauses,
+            vec![
+                clause(false, QueryTerm::Text("rust".to_string())),
+                clause(false, QueryTerm::Text("\"exact phrase\"".to_string())),
+                clause(false, QueryTerm::Title("async".to_string())),
+                clause(true, QueryTerm::Tag("read later".to_string())),
+                clause(false, QueryTerm::Site("github.com".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_unknown_keys_and_urls_as_text() {
+        let query = parse_query("https://example.com/a foo:bar").unwrap();
+        assert_eq!(
+            query.clauses,
+            vec![
+                clause(false, QueryTerm::Text("https://example.com/a".to_string())),
+                clause(false, QueryTerm::Text("foo:bar".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_dates_and_timestamps() {
+        let query = parse_query("after:2024-01-01 before:1700000000").unwrap();
+        assert_eq!(
+            query.clauses,
+            vec![
+                clause(false, QueryTerm::After(1704067200)),
+                clause(false, QueryTerm::Before(1700000000)),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_lone_dash_is_not_a_negation() {
+        let query = parse_query("- word").unwrap();
+        assert_eq!(
+            query.clauses,
+            vec![
+                clause(false, QueryTerm::Text("-".to_string())),
+                clause(false, QueryTerm::Text("word".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_where_the_query_is_invalid() {
+        let error = parse_query("rust \"open").unwrap_err();
+        assert_eq!(error.kind, QueryParseErrorKind::UnterminatedQuote);
+        assert_eq!((error.position, error.length), (5, 5));
+
+        let error = parse_query("tag:").unwrap_err();
+        assert_eq!(error.kind, QueryParseErrorKind::MissingValue);
+
+        let error = parse_query("x after:2024-13-01").unwrap_err();
+        assert_eq!(error.kind, QueryParseErrorKind::InvalidDate);
+        assert_eq!((error.position, error.length), (2, 16));
+    }
+}
@@ -1,5 +1,13 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    bookmark_tags (bookmark_id, tag_id) {
+        bookmark_id -> Integer,
+        tag_id -> Integer,
+        import_id -> Nullable<Integer>,
+    }
+}
+
 diesel::table! {
     bookmarks_table (id) {
         id -> Integer,
@@ -7,20 +15,57 @@ diesel::table! {
         link -> Text,
         icon_link -> Nullable<Text>,
         created_at -> BigInt,
+        canonical_link -> Text,
+        import_id -> Nullable<Integer>,
     }
 }
 
 diesel::table! {
-    tags_table (id) {
-        id -> Integer,
+    import_overwrites (import_id, bookmark_id) {
+        import_id -> Integer,
         bookmark_id -> Integer,
-        tag_name -> Text,
+        title -> Nullable<Text>,
+        link -> Text,
+        icon_link -> Nullable<Text>,
+        created_at -> BigInt,
+        canonical_link -> Text,
+        tags -> Text,
+    }
+}
+
+diesel::table! {
+    imports (id) {
+        id -> Integer,
+        source_file -> Text,
+        parser_name -> Text,
+        created_at -> BigInt,
+        inserted -> Integer,
+        skipped -> Integer,
+        merged -> Integer,
+        updated -> Integer,
+        failed -> Integer,
+    }
+}
+
+diesel::table! {
+    tags (id) {
+        id -> Integer,
+        name -> Text,
+        colour -> Nullable<Text>,
+        description -> Nullable<Text>,
+        created_at -> BigInt,
+        parent_id -> Nullable<Integer>,
     }
 }
 
-diesel::joinable!(tags_table -> bookmarks_table (bookmark_id));
+diesel::joinable!(bookmark_tags -> bookmarks_table (bookmark_id));
+diesel::joinable!(bookmark_tags -> tags (tag_id));
+diesel::joinable!(import_overwrites -> imports (import_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    bookmark_tags,
     bookmarks_table,
-    tags_table,
+    import_overwrites,
+    imports,
+    tags,
 );
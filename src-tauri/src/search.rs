@@ -0,0 +1,140 @@
+use diesel::dsl::sql;
+use diesel::expression::BoxableExpression;
+use diesel::sql_types::{Bool, Double, Text};
+use diesel::sqlite::Sqlite;
+
+use crate::schema::bookmarks_table;
+
+pub type SearchFilter = Box<dyn BoxableExpression<bookmarks_table::table, Sqlite, SqlType = Bool>>;
+pub type SearchRank = Box<dyn BoxableExpression<bookmarks_table::table, Sqlite, SqlType = Double>>;
+
+// Convert the text typed in the search box into an FTS5 MATCH expression.
+// Every word is quoted so FTS5 operators can't leak in from user input,
+// `word*` becomes a prefix query and `"some words"` becomes a phrase query.
+// Returns None when there is nothing left to search for.
+pub fn to_match_query(input: &str) -> Option<String> {
+    let mut terms: Vec<String> = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+
+        if c == '"' {
+            // Phrase: everything up to the closing quote (or the end of input)
+            let mut phrase = String::new();
+            for c in chars.by_ref() {
+                if c == '"' {
+                    break;
+                }
+                phrase.push(c);
+            }
+            let words: Vec<String> = phrase.split_whitespace().map(sanitize_word).collect();
+            let words: Vec<String> = words.into_iter().filter(|w| !w.is_empty()).collect();
+            if words.is_empty() {
+                continue;
+            }
+            let mut term = format!("\"{}\"", words.join(" "));
+            if chars.peek() == Some(&'*') {
+                chars.next();
+                term.push('*');
+            }
+            terms.push(term);
+        } else {
+            // Bare word: everything up to the next whitespace or quote
+            let mut word = String::from(c);
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == '"' {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            let is_prefix = word.ends_with('*');
+            let word = sanitize_word(&word);
+            if word.is_empty() {
+                continue;
+            }
+            if is_prefix {
+                terms.push(format!("\"{}\"*", word));
+            } else {
+                terms.push(format!("\"{}\"", word));
+            }
+        }
+    }
+
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+// Drop the characters that carry meaning inside an FTS5 string
+fn sanitize_word(word: &str) -> String {
+    word.chars().filter(|c| *c != '"' && *c != '*').collect()
+}
+
+// Restrict a bookmarks query to the rows matching an FTS5 expression
+pub fn matches(match_query: &str) -> SearchFilter {
+    Box::new(
+        sql::<Bool>(
+            "bookmarks_table.id IN (SELECT rowid FROM bookmarks_fts WHERE bookmarks_fts MATCH ",
+        )
+        .bind::<Text, _>(match_query.to_string())
+        .sql(")"),
+    )
+}
+
+// bm25 score of every bookmark for an FTS5 expression, lower is a better match
+pub fn rank(match_query: &str) -> SearchRank {
+    Box::new(
+        sql::<Double>("(SELECT bm25(bookmarks_fts) FROM bookmarks_fts WHERE bookmarks_fts MATCH ")
+            .bind::<Text, _>(match_query.to_string())
+            .sql(" AND bookmarks_fts.rowid = bookmarks_table.id)"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_every_word() {
+        assert_eq!(
+            to_match_query("rust  async"),
+            Some("\"rust\" \"async\"".to_string())
+        );
+    }
+
+    #[test]
+    fn keeps_prefixes_and_phrases() {
+        assert_eq!(to_match_query("tok*"), Some("\"tok\"*".to_string()));
+        assert_eq!(
+            to_match_query("\"hello world\" x"),
+            Some("\"hello world\" \"x\"".to_string())
+        );
+        assert_eq!(
+            to_match_query("\"hello wor\"*"),
+            Some("\"hello wor\"*".to_string())
+        );
+    }
+
+    #[test]
+    fn strips_fts_syntax() {
+        assert_eq!(to_match_query("a\"b"), Some("\"a\" \"b\"".to_string()));
+        assert_eq!(to_match_query("NEAR(a"), Some("\"NEAR(a\"".to_string()));
+        assert_eq!(
+            to_match_query("\"unclosed"),
+            Some("\"unclosed\"".to_string())
+        );
+    }
+
+    #[test]
+    fn nothing_to_search_for() {
+        assert_eq!(to_match_query(""), None);
+        assert_eq!(to_match_query("   "), None);
+        assert_eq!(to_match_query("* \"\" \"*\""), None);
+    }
+}
@@ -0,0 +1,517 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use serde_json::Value;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use crate::custom_parsers::{Parser, ParserSuccess};
+use crate::models::BookmarkNew;
+use crate::netscape_parser::{
+    attribute, element_text, parse_add_date, tokenize, NetscapeHtmlParser, Token,
+};
+use crate::parser_errors::ParserError;
+use crate::structs::{ParseFailBookmark, ParsedBookmarkWithTags, ParserConfig, ScriptOptions};
+
+// Read-later and bookmarking services with a built-in importer for their export.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Service {
+    // ril_export.html or the newer CSV export
+    Pocket,
+    // JSON export from the settings page
+    Pinboard,
+    // CSV export of a collection or of everything
+    Raindrop,
+    // Netscape HTML export with a TAGS attribute. Linkding separates the tags with commas,
+    // Shaarli with spaces, neither allows either in a tag.
+    LinkdingShaarli,
+}
+
+pub struct ServiceExportParser {
+    name: String,
+    r#type: String,
+    path: String,
+    supported_formats: Vec<String>,
+    service: Service,
+}
+
+impl ServiceExportParser {
+    pub fn new(service: Service) -> Self {
+        let (name, supported_formats) = match service {
+            Service::Pocket => ("Pocket", vec!["html", "csv"]),
+            Service::Pinboard => ("Pinboard", vec!["json"]),
+            Service::Raindrop => ("Raindrop", vec!["csv"]),
+            Service::LinkdingShaarli => ("Linkding / Shaarli", vec!["html", "htm"]),
+        };
+
+        ServiceExportParser {
+            name: name.to_string(),
+            r#type: "default".to_string(),
+            path: "In app".to_string(),
+            supported_formats: supported_formats.iter().map(|f| f.to_string()).collect(),
+            service,
+        }
+    }
+}
+
+fn split_tags(tags: &str, separator: impl Fn(char) -> bool) -> Vec<String> {
+    tags.split(separator)
+        .map(|tag| tag.trim())
+        .filter(|tag| !tag.is_empty())
+        .map(|tag| tag.to_string())
+        .collect()
+}
+
+fn non_empty(value: Option<&str>) -> Option<String> {
+    value
+        .map(|value| value.trim())
+        .filter(|value| !value.is_empty())
+        .map(|value| value.to_string())
+}
+
+fn parse_rfc3339(value: &str) -> Option<i64> {
+    OffsetDateTime::parse(value.trim(), &Rfc3339)
+        .ok()
+        .map(|date| date.unix_timestamp())
+}
+
+// A CSV record keyed by lowercased header, or why it couldn't be read
+type CsvRow = Result<HashMap<String, String>, String>;
+
+// Every record of a CSV export. Fails when one of the `required` columns is missing.
+fn read_csv(content: &str, required: &[&str], service: &str) -> Result<Vec<CsvRow>, ParserError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(content.as_bytes());
+
+    let headers: Vec<String> = reader
+        .headers()
+        .map_err(|e| ParserError::InvalidFormat(format!("Invalid CSV header: {}", e)))?
+        .iter()
+        .map(|header| header.trim().to_lowercase())
+        .collect();
+
+    if let Some(missing) = required
+        .iter()
+        .find(|column| !headers.iter().any(|header| header == *column))
+    {
+        return Err(ParserError::InvalidFormat(format!(
+            "Not a {} export, missing the {} column",
+            service, missing
+        )));
+    }
+
+    Ok(reader
+        .records()
+        .map(|record| {
+            record
+                .map(|record| {
+                    headers
+                        .iter()
+                        .cloned()
+                        .zip(record.iter().map(String::from))
+                        .collect()
+                })
+                .map_err(|e| e.to_string())
+        })
+        .collect())
+}
+
+// title,url,time_added,tags,status with tags separated by |
+fn parse_pocket_csv(content: &str) -> Result<ParserSuccess, ParserError> {
+    let mut successful: Vec<ParsedBookmarkWithTags> = Vec::new();
+    let mut failed: Vec<ParseFailBookmark> = Vec::new();
+
+    for (index, row) in read_csv(content, &["url", "time_added"], "Pocket")?
+        .into_iter()
+        .enumerate()
+    {
+        let row = match row {
+            Ok(row) => row,
+            Err(e) => {
+                failed.push(ParseFailBookmark::new(index as i32, String::new(), e));
+                continue;
+            }
+        };
+        let item = || format!("{:?}", row);
+        let get = |column: &str| row.get(column).map(|value| value.as_str());
+
+        let link = match non_empty(get("url")) {
+            Some(link) => link,
+            None => {
+                failed.push(ParseFailBookmark::new(
+                    index as i32,
+                    item(),
+                    "Missing url".to_string(),
+                ));
+                continue;
+            }
+        };
+        let created_at = match get("time_added").and_then(parse_add_date) {
+            Some(created_at) => created_at,
+            None => {
+                failed.push(ParseFailBookmark::new(
+                    index as i32,
+                    item(),
+                    "Invalid time_added".to_string(),
+                ));
+                continue;
+            }
+        };
+
+        successful.push(ParsedBookmarkWithTags {
+            bookmark: BookmarkNew {
+                title: non_empty(get("title")),
+                link,
+                icon_link: None,
+                created_at,
+            },
+            tags: split_tags(get("tags").unwrap_or_default(), |c| c == '|'),
+        });
+    }
+
+    Ok(ParserSuccess::new(successful, failed))
+}
+
+// <li><a href="..." time_added="..." tags="a,b">Title</a></li> under <h1>Unread</h1> and <h1>Read Archive</h1>
+fn parse_pocket_html(content: &str) -> Result<ParserSuccess, ParserError> {
+    let tokens = tokenize(content);
+
+    let mut successful: Vec<ParsedBookmarkWithTags> = Vec::new();
+    let mut failed: Vec<ParseFailBookmark> = Vec::new();
+    let mut index = 0;
+
+    for (position, token) in tokens.iter().enumerate() {
+        let (attributes, raw) = match token {
+            Token::Open {
+                name,
+                attributes,
+                raw,
+            } if name == "a" => (attributes, raw),
+            _ => continue,
+        };
+        let entry_index = index;
+        index += 1;
+
+        let fail =
+            |error: &str| ParseFailBookmark::new(entry_index, raw.to_string(), error.to_string());
+
+        let title = match element_text(&tokens, position + 1, "a") {
+            Some((title, _)) => title,
+            None => {
+                failed.push(fail("Link is never closed with </a>"));
+                continue;
+            }
+        };
+        let link = match non_empty(attribute(attributes, "href").as_deref()) {
+            Some(link) => link,
+            None => {
+                failed.push(fail("Missing href"));
+                continue;
+            }
+        };
+        let created_at = match attribute(attributes, "time_added")
+            .as_deref()
+            .and_then(parse_add_date)
+        {
+            Some(created_at) => created_at,
+            None => {
+                failed.push(fail("Invalid time_added"));
+                continue;
+            }
+        };
+
+        successful.push(ParsedBookmarkWithTags {
+            bookmark: BookmarkNew {
+                title: non_empty(Some(&title)),
+                link,
+                icon_link: None,
+                created_at,
+            },
+            tags: split_tags(&attribute(attributes, "tags").unwrap_or_default(), |c| {
+                c == ','
+            }),
+        });
+    }
+
+    if index == 0 {
+        return Err(ParserError::InvalidFormat(
+            "Not a Pocket export, no links found".to_string(),
+        ));
+    }
+
+    Ok(ParserSuccess::new(successful, failed))
+}
+
+// [{"href": ..., "description": title, "time": RFC 3339, "tags": "space separated"}, ...]
+fn parse_pinboard_json(content: &str) -> Result<ParserSuccess, ParserError> {
+    let entries: Vec<Value> = serde_json::from_str(content)
+        .map_err(|e| ParserError::InvalidFormat(format!("Not a Pinboard export: {}", e)))?;
+
+    let mut successful: Vec<ParsedBookmarkWithTags> = Vec::new();
+    let mut failed: Vec<ParseFailBookmark> = Vec::new();
+
+    for (index, entry) in entries.iter().enumerate() {
+        let get = |field: &str| entry.get(field).and_then(|value| value.as_str());
+        let fail = |error: &str| {
+            ParseFailBookmark::new(index as i32, entry.to_string(), error.to_string())
+        };
+
+        let link = match non_empty(get("href")) {
+            Some(link) => link,
+            None => {
+                failed.push(fail("Missing href"));
+                continue;
+            }
+        };
+        let created_at = match get("time").and_then(parse_rfc3339) {
+            Some(created_at) => created_at,
+            None => {
+                failed.push(fail("Invalid time"));
+                continue;
+            }
+        };
+
+        successful.push(ParsedBookmarkWithTags {
+            bookmark: BookmarkNew {
+                title: non_empty(get("description")),
+                link,
+                icon_link: None,
+                created_at,
+            },
+            tags: split_tags(get("tags").unwrap_or_default(), char::is_whitespace),
+        });
+    }
+
+    Ok(ParserSuccess::new(successful, failed))
+}
+
+// id,title,note,excerpt,url,folder,tags,created,cover,... with comma separated tags.
+// The folder (collection) becomes a tag too, except for the default "Unsorted" one.
+fn parse_raindrop_csv(content: &str) -> Result<ParserSuccess, ParserError> {
+    let mut successful: Vec<ParsedBookmarkWithTags> = Vec::new();
+    let mut failed: Vec<ParseFailBookmark> = Vec::new();
+
+    for (index, row) in read_csv(content, &["url", "created"], "Raindrop")?
+        .into_iter()
+        .enumerate()
+    {
+        let row = match row {
+            Ok(row) => row,
+            Err(e) => {
+                failed.push(ParseFailBookmark::new(index as i32, String::new(), e));
+                continue;
+            }
+        };
+        let item = || format!("{:?}", row);
+        let get = |column: &str| row.get(column).map(|value| value.as_str());
+
+        let link = match non_empty(get("url")) {
+            Some(link) => link,
+            None => {
+                failed.push(ParseFailBookmark::new(
+                    index as i32,
+                    item(),
+                    "Missing url".to_string(),
+                ));
+                continue;
+            }
+        };
+        let created_at = match get("created").and_then(parse_rfc3339) {
+            Some(created_at) => created_at,
+            None => {
+                failed.push(ParseFailBookmark::new(
+                    index as i32,
+                    item(),
+                    "Invalid created date".to_string(),
+                ));
+                continue;
+            }
+        };
+
+        let mut tags: Vec<String> = Vec::new();
+        if let Some(folder) = non_empty(get("folder")).filter(|folder| folder != "Unsorted") {
+            tags.push(folder);
+        }
+        tags.extend(split_tags(get("tags").unwrap_or_default(), |c| c == ','));
+
+        successful.push(ParsedBookmarkWithTags {
+            bookmark: BookmarkNew {
+                title: non_empty(get("title")),
+                // `cover` is the page's preview image, not its favicon
+                icon_link: None,
+                link,
+                created_at,
+            },
+            tags,
+        });
+    }
+
+    Ok(ParserSuccess::new(successful, failed))
+}
+
+// Netscape HTML whose TAGS attribute may separate the tags with commas or spaces
+fn parse_linkding_shaarli_html(content: &str) -> Result<ParserSuccess, ParserError> {
+    NetscapeHtmlParser::new()
+        .with_tag_separator(|c| c == ',' || c.is_whitespace())
+        .parse_content(content)
+}
+
+impl Parser for ServiceExportParser {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn parse(&self, input_path: &str) -> Result<ParserSuccess, ParserError> {
+        let content = fs::read_to_string(input_path)
+            .map_err(|e| ParserError::FileReadError(format!("Error reading file: {}", e)))?;
+        let is_csv = Path::new(input_path)
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("csv"));
+
+        match self.service {
+            Service::Pocket if is_csv => parse_pocket_csv(&content),
+            Service::Pocket => parse_pocket_html(&content),
+            Service::Pinboard => parse_pinboard_json(&content),
+            Service::Raindrop => parse_raindrop_csv(&content),
+            Service::LinkdingShaarli => parse_linkding_shaarli_html(&content),
+        }
+    }
+
+    fn supported_formats(&self) -> Vec<&str> {
+        self.supported_formats.iter().map(|s| s.as_str()).collect()
+    }
+
+    fn info(&self) -> ParserConfig {
+        ParserConfig {
+            name: self.name.to_string(),
+            r#type: self.r#type.to_string(),
+            path: self.path.to_string(),
+            supported_formats: self.supported_formats.clone(),
+            script: ScriptOptions::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_pocket_csv() {
+        let csv = "title,url,time_added,tags,status\n\
+            Rust,https://www.rust-lang.org/,1700000000,lang|systems,unread\n\
+            ,https://example.com/,1700000000,,archive\n\
+            No link,,1700000000,,unread\n\
+            Bad date,https://example.org/,soon,,unread\n";
+        let result = parse_pocket_csv(csv).unwrap();
+        let successful = result.get_successful();
+        assert_eq!(successful.len(), 2);
+        assert_eq!(result.get_failed().len(), 2);
+
+        assert_eq!(successful[0].bookmark.title.as_deref(), Some("Rust"));
+        assert_eq!(successful[0].bookmark.link, "https://www.rust-lang.org/");
+        assert_eq!(successful[0].bookmark.created_at, 1700000000);
+        assert_eq!(successful[0].tags, vec!["lang", "systems"]);
+
+        assert_eq!(successful[1].bookmark.title, None);
+        assert!(successful[1].tags.is_empty());
+    }
+
+    #[test]
+    fn rejects_csv_without_the_required_columns() {
+        assert!(parse_pocket_csv("title,link\nRust,https://www.rust-lang.org/\n").is_err());
+        assert!(parse_raindrop_csv("title,url\nRust,https://www.rust-lang.org/\n").is_err());
+    }
+
+    #[test]
+    fn reads_pocket_html() {
+        let html = r#"<!DOCTYPE html>
+<html><body>
+<h1>Unread</h1>
+<ul>
+<li><a href="https://www.rust-lang.org/" time_added="1700000000" tags="lang,systems">Rust &amp; Cargo</a></li>
+<li><a time_added="1700000000">No link</a></li>
+</ul>
+<h1>Read Archive</h1>
+<ul>
+<li><a href="https://example.com/" time_added="1700000001" tags="">Example</a></li>
+</ul>
+</body></html>"#;
+        let result = parse_pocket_html(html).unwrap();
+        let successful = result.get_successful();
+        assert_eq!(successful.len(), 2);
+        assert_eq!(result.get_failed().len(), 1);
+
+        assert_eq!(
+            successful[0].bookmark.title.as_deref(),
+            Some("Rust & Cargo")
+        );
+        assert_eq!(successful[0].tags, vec!["lang", "systems"]);
+        assert_eq!(successful[1].bookmark.created_at, 1700000001);
+        assert!(successful[1].tags.is_empty());
+    }
+
+    #[test]
+    fn rejects_html_without_links() {
+        assert!(parse_pocket_html("<html><body><h1>Unread</h1></body></html>").is_err());
+    }
+
+    #[test]
+    fn reads_pinboard_json() {
+        let json = r#"[
+            {"href": "https://www.rust-lang.org/", "description": "Rust", "time": "2023-11-14T22:13:20Z", "tags": "lang  systems"},
+            {"href": "https://example.com/", "description": "", "time": "2023-11-14T23:13:20+01:00", "tags": ""},
+            {"description": "No link", "time": "2023-11-14T22:13:20Z"},
+            {"href": "https://example.org/", "time": "yesterday"}
+        ]"#;
+        let result = parse_pinboard_json(json).unwrap();
+        let successful = result.get_successful();
+        assert_eq!(successful.len(), 2);
+        assert_eq!(result.get_failed().len(), 2);
+
+        assert_eq!(successful[0].bookmark.title.as_deref(), Some("Rust"));
+        assert_eq!(successful[0].bookmark.created_at, 1700000000);
+        assert_eq!(successful[0].tags, vec!["lang", "systems"]);
+
+        assert_eq!(successful[1].bookmark.title, None);
+        assert_eq!(successful[1].bookmark.created_at, 1700000000);
+    }
+
+    #[test]
+    fn rejects_other_json() {
+        assert!(parse_pinboard_json(r#"{"bookmarks": []}"#).is_err());
+    }
+
+    #[test]
+    fn reads_raindrop_csv() {
+        let csv = "id,title,note,excerpt,url,folder,tags,created,cover\n\
+            1,Rust,,,https://www.rust-lang.org/,Dev,\"lang, systems\",2023-11-14T22:13:20.000Z,https://img/\n\
+            2,Example,,,https://example.com/,Unsorted,,2023-11-14T22:13:20.000Z,\n\
+            3,Bad date,,,https://example.org/,Dev,,soon,\n";
+        let result = parse_raindrop_csv(csv).unwrap();
+        let successful = result.get_successful();
+        assert_eq!(successful.len(), 2);
+        assert_eq!(result.get_failed().len(), 1);
+
+        assert_eq!(successful[0].bookmark.created_at, 1700000000);
+        assert_eq!(successful[0].bookmark.icon_link, None);
+        assert_eq!(successful[0].tags, vec!["Dev", "lang", "systems"]);
+
+        assert!(successful[1].tags.is_empty());
+    }
+
+    #[test]
+    fn reads_linkding_and_shaarli_tags() {
+        let html = r#"<DL><p>
+<DT><A HREF="https://www.rust-lang.org/" ADD_DATE="1700000000" TAGS="lang,systems">Rust</A>
+<DT><A HREF="https://example.com/" ADD_DATE="1700000000" TAGS="web example">Example</A>
+</DL><p>"#;
+        let result = parse_linkding_shaarli_html(html).unwrap();
+        let successful = result.get_successful();
+        assert_eq!(successful.len(), 2);
+        assert_eq!(successful[0].tags, vec!["lang", "systems"]);
+        assert_eq!(successful[1].tags, vec!["web", "example"]);
+    }
+}
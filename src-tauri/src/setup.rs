@@ -1,13 +1,18 @@
 use crate::structs::{AppData, AppDataStorage};
-use crate::utils::read_app_data_from_storage;
-use crate::utils::register_parsers;
+use crate::utils::{broadcast_info, read_app_data_from_storage};
+use crate::utils::{register_exporters, register_parsers};
 use std::fs::create_dir;
 use std::path::PathBuf;
 use std::sync::Mutex;
 use tauri::{AppHandle, Manager};
 use tauri_plugin_deep_link::DeepLinkExt;
 
+use crate::bookmark_stream::BookmarkStreams;
 use crate::custom_parsers::ParserRegistry;
+use crate::database_cmds::prepare_db;
+use crate::exporters::ExporterRegistry;
+use crate::import_jobs::ImportJobs;
+use crate::import_preview::ImportPreviews;
 use crate::tray;
 
 fn init_app_state(app: &AppHandle) {
@@ -28,15 +33,43 @@ fn init_app_state(app: &AppHandle) {
 
     let app_data_from_storage = read_app_data_from_storage(default_config_path, config_path);
 
-    app.manage(Mutex::new(AppData::from_storage(
-        app_data_from_storage.clone(),
-    )));
+    let mut app_data = AppData::from_storage(app_data_from_storage.clone());
+
+    // Bring a database created by an older version up to date before anything queries it.
+    // One that can't be migrated isn't opened, the app starts on the database picker instead.
+    if !app_data.db_path.is_empty() {
+        let prepared = app_data
+            .db_pool
+            .get()
+            .map_err(|e| format!("Error opening database: {}", e))
+            .and_then(|mut conn| prepare_db(&mut conn, &app_data.tracking_params));
+        if let Err(e) = prepared {
+            broadcast_info("Database Error", &e, log::Level::Error, true);
+            app_data.db_path = String::new();
+        }
+    }
+
+    app.manage(Mutex::new(app_data));
 
     let mut registry = ParserRegistry::new();
 
     register_parsers(&app_data_from_storage.custom_parsers, &mut registry);
 
     app.manage(Mutex::new(registry));
+
+    let mut exporter_registry = ExporterRegistry::new();
+
+    register_exporters(
+        &app_data_from_storage.custom_exporters,
+        &mut exporter_registry,
+    );
+
+    app.manage(Mutex::new(exporter_registry));
+
+    app.manage(Mutex::new(BookmarkStreams::new()));
+
+    app.manage(Mutex::new(ImportPreviews::new()));
+    app.manage(Mutex::new(ImportJobs::new()));
 }
 
 pub async fn setup_tasks(app: AppHandle) -> Result<(), ()> {
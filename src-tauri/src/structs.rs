@@ -3,6 +3,8 @@ use std::collections::HashMap;
 use crate::{
     database_cmds::{self, DbPool},
     models::{Bookmark, BookmarkNew, Tag},
+    query_parser::QueryParseError,
+    url_canonical::default_tracking_params,
 };
 use serde::{Deserialize, Serialize};
 
@@ -51,6 +53,14 @@ pub struct BookmarkWithTags {
     pub tags: Vec<String>,
 }
 
+// Keys are snake_case like the flattened tag's
+#[derive(Serialize, Debug, Clone)]
+pub struct TagWithCount {
+    #[serde(flatten)]
+    pub tag: Tag,
+    pub bookmark_count: i64,
+}
+
 #[derive(Serialize, Debug, Clone, Deserialize)]
 pub struct ParsedBookmarkWithTags {
     #[serde(flatten)]
@@ -65,6 +75,12 @@ pub struct ParseFailBookmark {
     error: String,
 }
 
+impl ParseFailBookmark {
+    pub fn new(index: i32, item: String, error: String) -> Self {
+        ParseFailBookmark { index, item, error }
+    }
+}
+
 #[derive(Serialize, Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct BookmarkQueryResponse {
@@ -72,6 +88,9 @@ pub struct BookmarkQueryResponse {
     pub total_count: i64,
     pub total_pages: i64,
     pub page: i64,
+    // Only set in cursor mode, None once the last page has been returned
+    pub next_cursor: Option<String>,
+    pub parse_error: Option<QueryParseError>,
 }
 
 impl Default for BookmarkQueryResponse {
@@ -81,6 +100,8 @@ impl Default for BookmarkQueryResponse {
             total_count: 0,
             total_pages: 0,
             page: 0,
+            next_cursor: None,
+            parse_error: None,
         }
     }
 }
@@ -92,12 +113,142 @@ pub struct ParserConfig {
     pub r#type: String,
     pub path: String,
     pub supported_formats: Vec<String>,
+    // Only used by script parsers, kept flat in the config
+    #[serde(flatten)]
+    pub script: ScriptOptions,
+}
+
+// How a custom parser's script is run
+#[derive(Serialize, Debug, Clone, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptOptions {
+    // How the script prints its results, json when not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<ParserProtocol>,
+    // Seconds the script may run before it is killed, no limit when not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+    // Import the script into the app's own Python and call its parse(path) function
+    // instead of running it in a separate interpreter
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub in_process: bool,
+    // Interpreter to run the script with, e.g. the python of a virtualenv.
+    // python3 or python from PATH when not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interpreter: Option<String>,
+    // Passed to the script after the input path
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    // Added to the app's environment
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub env: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub working_dir: Option<String>,
+    // Limits of the Lua VM, defaults in lua_parser.rs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_limit_mb: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instruction_limit: Option<u64>,
+}
+
+// Output format of custom parser scripts
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ParserProtocol {
+    // A single {"successful": [...], "failed": [...]} object
+    #[default]
+    Json,
+    // One success, failure or progress record per line, read as the script prints them
+    Jsonl,
+}
+
+#[derive(Serialize, Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExporterConfig {
+    pub name: String,
+    pub r#type: String,
+    pub path: String,
+    // File extension of the output, without the dot
+    pub format: String,
+    #[serde(flatten)]
+    pub script: ExporterOptions,
+}
+
+// How a custom exporter's script is run, the subset of ScriptOptions that applies to it
+#[derive(Serialize, Debug, Clone, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ExporterOptions {
+    // Seconds the script may run before it is killed, no limit when not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+    // python3 or python from PATH when not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interpreter: Option<String>,
+    // Passed to the script after the output path
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    // Added to the app's environment
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub env: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub working_dir: Option<String>,
+}
+
+// What to do when a bookmark's canonical link is already stored
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicatePolicy {
+    // Keep the existing bookmark untouched
+    Skip,
+    // Add the new tags to the existing bookmark
+    #[default]
+    MergeTags,
+    // Overwrite the existing bookmark and its tags
+    Update,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum InsertOutcome {
+    Inserted,
+    Skipped,
+    MergedTags,
+    Updated,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct InsertCounts {
+    pub inserted: usize,
+    pub skipped: usize,
+    pub merged: usize,
+    pub updated: usize,
+}
+
+impl InsertCounts {
+    // Bookmarks handled so far, whatever happened to them
+    pub fn processed(&self) -> usize {
+        self.inserted + self.skipped + self.merged + self.updated
+    }
+
+    pub fn add(&mut self, outcome: InsertOutcome) {
+        match outcome {
+            InsertOutcome::Inserted => self.inserted += 1,
+            InsertOutcome::Skipped => self.skipped += 1,
+            InsertOutcome::MergedTags => self.merged += 1,
+            InsertOutcome::Updated => self.updated += 1,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AppDataStorage {
     pub db_path: String,
     pub custom_parsers: Vec<ParserConfig>,
+    #[serde(default)]
+    pub custom_exporters: Vec<ExporterConfig>,
+    #[serde(default = "default_tracking_params")]
+    pub tracking_params: Vec<String>,
+    #[serde(default)]
+    pub duplicate_policy: DuplicatePolicy,
 }
 
 impl AppDataStorage {
@@ -119,9 +270,33 @@ impl AppDataStorage {
             })
             .unwrap_or_default();
 
+        let custom_exporters: Vec<ExporterConfig> = storage
+            .get("custom_exporters")
+            .and_then(|value| value.as_array())
+            .map(|value| {
+                value
+                    .iter()
+                    .filter_map(|exporter| serde_json::from_value(exporter.clone()).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let tracking_params: Vec<String> = storage
+            .get("tracking_params")
+            .and_then(|value| serde_json::from_value(value.clone()).ok())
+            .unwrap_or_else(default_tracking_params);
+
+        let duplicate_policy: DuplicatePolicy = storage
+            .get("duplicate_policy")
+            .and_then(|value| serde_json::from_value(value.clone()).ok())
+            .unwrap_or_default();
+
         AppDataStorage {
             db_path,
             custom_parsers,
+            custom_exporters,
+            tracking_params,
+            duplicate_policy,
         }
     }
 }
@@ -130,6 +305,8 @@ impl AppDataStorage {
 pub struct AppData {
     pub db_pool: DbPool,
     pub db_path: String,
+    pub tracking_params: Vec<String>,
+    pub duplicate_policy: DuplicatePolicy,
 }
 
 impl AppData {
@@ -138,6 +315,8 @@ impl AppData {
         AppData {
             db_pool: database_cmds::establish_connection_pool(&storage.db_path),
             db_path: storage.db_path,
+            tracking_params: storage.tracking_params,
+            duplicate_policy: storage.duplicate_policy,
         }
     }
 }
@@ -147,6 +326,9 @@ impl Default for AppDataStorage {
         AppDataStorage {
             db_path: "".to_string(),
             custom_parsers: vec![],
+            custom_exporters: vec![],
+            tracking_params: default_tracking_params(),
+            duplicate_policy: DuplicatePolicy::default(),
         }
     }
 }
@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+
+use diesel::prelude::*;
+use diesel::sql_types::{Integer, Text};
+use diesel::sqlite::Sqlite;
+use serde::Serialize;
+
+use crate::models::Tag;
+use crate::schema::tags;
+
+// Separates the levels of a nested tag, e.g. "dev/rust/async"
+pub const TAG_SEPARATOR: char = '/';
+
+pub type TagIdsQuery<'a> = tags::BoxedQuery<'a, Sqlite, Integer>;
+
+// Keys are snake_case like the flattened tag's
+#[derive(Serialize, Debug, Clone)]
+pub struct TagTreeNode {
+    #[serde(flatten)]
+    pub tag: Tag,
+    pub bookmark_count: i64,
+    pub children: Vec<TagTreeNode>,
+}
+
+// Trim every level of a tag path and drop the empty ones, " Dev / Rust//Async " becomes "Dev/Rust/Async".
+// Returns None when nothing is left.
+pub fn normalize_tag_path(name: &str) -> Option<String> {
+    let levels: Vec<&str> = name
+        .split(TAG_SEPARATOR)
+        .map(|level| level.trim())
+        .filter(|level| !level.is_empty())
+        .collect();
+
+    if levels.is_empty() {
+        None
+    } else {
+        Some(levels.join(&TAG_SEPARATOR.to_string()))
+    }
+}
+
+// Path of the tag one level up, None for top level tags
+pub fn parent_path(path: &str) -> Option<&str> {
+    path.rsplit_once(TAG_SEPARATOR).map(|(parent, _)| parent)
+}
+
+// Whether `path` is nested somewhere below `ancestor`
+pub fn is_descendant(path: &str, ancestor: &str) -> bool {
+    path.len() > ancestor.len()
+        && path.starts_with(ancestor)
+        && path[ancestor.len()..].starts_with(TAG_SEPARATOR)
+}
+
+// LIKE ignores ASCII case, nested names are compared on the exact prefix instead
+diesel::define_sql_function!(fn substr(x: Text, start: Integer, length: Integer) -> Text);
+diesel::define_sql_function!(fn length(x: Text) -> Integer);
+
+// Ids of the tags named `names` together with every tag nested under them
+pub fn subtree_ids<'a>(names: &[String]) -> TagIdsQuery<'a> {
+    let mut query = tags::table.select(tags::id).into_boxed();
+
+    for name in names {
+        let prefix = format!("{}{}", name, TAG_SEPARATOR);
+        query = query.or_filter(
+            tags::name
+                .eq(name.clone())
+                .or(substr(tags::name, 1, prefix.chars().count() as i32).eq(prefix)),
+        );
+    }
+
+    query
+}
+
+// Nest tags (with their bookmark counts) under their parents.
+// Siblings keep the order they were given in.
+pub fn build_tree(tags_with_counts: Vec<(Tag, i64)>) -> Vec<TagTreeNode> {
+    let mut children: HashMap<Option<i32>, Vec<(Tag, i64)>> = HashMap::new();
+    for (tag, bookmark_count) in tags_with_counts {
+        children
+            .entry(tag.parent_id)
+            .or_default()
+            .push((tag, bookmark_count));
+    }
+
+    collect_children(&mut children, None)
+}
+
+fn collect_children(
+    children: &mut HashMap<Option<i32>, Vec<(Tag, i64)>>,
+    parent_id: Option<i32>,
+) -> Vec<TagTreeNode> {
+    children
+        .remove(&parent_id)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(tag, bookmark_count)| {
+            let nested = collect_children(children, Some(tag.id));
+            TagTreeNode {
+                tag,
+                bookmark_count,
+                children: nested,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_levels() {
+        assert_eq!(
+            normalize_tag_path(" Dev / Rust//Async "),
+            Some("Dev/Rust/Async".to_string())
+        );
+        assert_eq!(normalize_tag_path("/rust/"), Some("rust".to_string()));
+        assert_eq!(
+            normalize_tag_path("read later"),
+            Some("read later".to_string())
+        );
+    }
+
+    #[test]
+    fn empty_paths_are_dropped() {
+        assert_eq!(normalize_tag_path(""), None);
+        assert_eq!(normalize_tag_path(" / // "), None);
+    }
+
+    #[test]
+    fn finds_parents_and_descendants() {
+        assert_eq!(parent_path("dev/rust/async"), Some("dev/rust"));
+        assert_eq!(parent_path("dev"), None);
+        assert!(is_descendant("dev/rust", "dev"));
+        assert!(!is_descendant("dev", "dev"));
+        assert!(!is_descendant("devops", "dev"));
+    }
+}
@@ -0,0 +1,99 @@
+use url::Url;
+
+// Query parameters that only track where a visit came from. A trailing `*` matches any suffix.
+pub const DEFAULT_TRACKING_PARAMS: &[&str] = &[
+    "utm_*", "fbclid", "gclid", "dclid", "msclkid", "yclid", "mc_cid", "mc_eid", "igshid",
+    "_hsenc", "_hsmi", "ref_src",
+];
+
+pub fn default_tracking_params() -> Vec<String> {
+    DEFAULT_TRACKING_PARAMS
+        .iter()
+        .map(|param| param.to_string())
+        .collect()
+}
+
+fn is_tracking_param(key: &str, tracking_params: &[String]) -> bool {
+    tracking_params
+        .iter()
+        .any(|param| match param.strip_suffix('*') {
+            Some(prefix) => key.starts_with(prefix),
+            None => key == param,
+        })
+}
+
+// Canonical form of a link used to recognise duplicates: lowercase host, no default port,
+// no tracking parameters and a sorted query string. Links that don't parse as URLs are only trimmed.
+pub fn canonicalize_url(link: &str, tracking_params: &[String]) -> String {
+    let link = link.trim();
+    let mut url = match Url::parse(link) {
+        Ok(url) => url,
+        Err(_) => return link.to_string(),
+    };
+
+    // The parser already lowercases the host and drops the default port of
+    // special schemes (http, https, ftp, ...), other schemes keep their host as written
+    if let Some(host) = url.host_str() {
+        let host = host.to_lowercase();
+        let _ = url.set_host(Some(&host));
+    }
+
+    let mut query: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(key, _)| !is_tracking_param(key, tracking_params))
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+    query.sort();
+
+    if query.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(query);
+    }
+
+    url.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_host_port_and_query() {
+        assert_eq!(
+            canonicalize_url(
+                " HTTPS://Example.COM:443/Path?b=2&a=1&utm_source=x#frag ",
+                &default_tracking_params()
+            ),
+            "https://example.com/Path?a=1&b=2#frag"
+        );
+    }
+
+    #[test]
+    fn drops_a_query_of_only_tracking_params() {
+        assert_eq!(
+            canonicalize_url(
+                "https://example.com/?utm_source=x&fbclid=y",
+                &default_tracking_params()
+            ),
+            "https://example.com/"
+        );
+    }
+
+    #[test]
+    fn uses_the_configured_tracking_params() {
+        let tracking_params = vec!["ref".to_string()];
+        assert_eq!(
+            canonicalize_url("https://example.com/?ref=a&utm_source=b", &tracking_params),
+            "https://example.com/?utm_source=b"
+        );
+    }
+
+    #[test]
+    fn keeps_links_that_are_not_urls() {
+        assert_eq!(
+            canonicalize_url("  not a url ", &default_tracking_params()),
+            "not a url"
+        );
+    }
+}
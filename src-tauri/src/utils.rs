@@ -1,6 +1,11 @@
+use crate::chromium_parser::ChromiumBookmarksParser;
 use crate::commands::{create_db, open_db};
-use crate::custom_parsers::{BrowserJsonParser, Parser, ParserRegistry, PythonParser};
-use crate::structs::{AppData, AppDataStorage, ParserConfig};
+use crate::custom_parsers::{custom_parser, BrowserJsonParser, Parser, ParserRegistry};
+use crate::exporters::{BuiltinExporter, ExportFormat, Exporter, ExporterRegistry, PythonExporter};
+use crate::firefox_places_parser::FirefoxPlacesParser;
+use crate::netscape_parser::NetscapeHtmlParser;
+use crate::service_parsers::{Service, ServiceExportParser};
+use crate::structs::{AppData, AppDataStorage, ExporterConfig, ParserConfig};
 use crate::tray::EXIT_FLAG;
 use config::{Config, File};
 use ctrlc;
@@ -65,20 +70,25 @@ pub fn read_app_data_from_storage(
 }
 
 pub fn write_app_data_to_storage(app_handle: &AppHandle) -> io::Result<()> {
-    let app_data_binding = app_handle.state::<Mutex<AppData>>();
-    let registry_binding = app_handle.state::<Mutex<ParserRegistry>>();
-    let app_data = app_data_binding.lock().unwrap();
-    let registry = registry_binding.lock().unwrap();
-
     let path = app_handle
         .path()
         .app_config_dir()
         .unwrap()
         .join("config.json");
 
+    // Each state is locked on its own, never while holding another one
     let mut storage = AppDataStorage::default();
-    storage.db_path = app_data.db_path.clone();
-    storage.custom_parsers = registry
+    {
+        let app_data = app_handle.state::<Mutex<AppData>>();
+        let app_data = app_data.lock().unwrap();
+        storage.db_path = app_data.db_path.clone();
+        storage.tracking_params = app_data.tracking_params.clone();
+        storage.duplicate_policy = app_data.duplicate_policy;
+    }
+    storage.custom_parsers = app_handle
+        .state::<Mutex<ParserRegistry>>()
+        .lock()
+        .unwrap()
         .parsers
         .values()
         .filter_map(|parser| {
@@ -89,6 +99,20 @@ pub fn write_app_data_to_storage(app_handle: &AppHandle) -> io::Result<()> {
             }
         })
         .collect();
+    storage.custom_exporters = app_handle
+        .state::<Mutex<ExporterRegistry>>()
+        .lock()
+        .unwrap()
+        .exporters
+        .values()
+        .filter_map(|exporter| {
+            if exporter.info().r#type != "default" {
+                Some(exporter.info())
+            } else {
+                None
+            }
+        })
+        .collect();
 
     // Convert AppDataStorage to JSON string
     let json_content = serde_json::to_string_pretty(&storage)
@@ -188,6 +212,64 @@ pub async fn watch_config<P: AsRef<Path>>(path: P, app_handle: AppHandle) -> not
     Ok(())
 }
 
+pub fn register_exporters(custom_exporters: &Vec<ExporterConfig>, registry: &mut ExporterRegistry) {
+    registry.remove_exporters();
+
+    for export_format in [
+        ExportFormat::NetscapeHtml,
+        ExportFormat::PcPocketJson,
+        ExportFormat::Csv,
+        ExportFormat::Markdown,
+    ] {
+        let default_exporter = BuiltinExporter::new(export_format);
+        registry
+            .register(
+                default_exporter.name().to_string(),
+                Box::new(default_exporter),
+            )
+            .unwrap();
+    }
+
+    for exporter_info in custom_exporters {
+        match exporter_info.r#type.as_str() {
+            "python" => match PythonExporter::new(exporter_info) {
+                Ok(exporter) => {
+                    match registry.register(exporter_info.name.clone(), Box::new(exporter)) {
+                        Ok(_) => {}
+                        Err(e) => {
+                            broadcast_info(
+                                "Exporter Registration Error",
+                                &format!("Failed to register exporter: {}", e),
+                                log::Level::Error,
+                                false,
+                            );
+                        }
+                    }
+                }
+                Err(e) => {
+                    broadcast_info(
+                        "Exporter Error",
+                        &format!(
+                            "Failed to load Python exporter from {}: {}",
+                            exporter_info.path, e
+                        ),
+                        log::Level::Error,
+                        false,
+                    );
+                }
+            },
+            _ => {
+                broadcast_info(
+                    "Exporter Error",
+                    &format!("Unsupported exporter type: {}", exporter_info.r#type),
+                    log::Level::Warn,
+                    false,
+                );
+            }
+        }
+    }
+}
+
 pub fn register_parsers(custom_parsers: &Vec<ParserConfig>, registry: &mut ParserRegistry) {
     registry.remove_parsers();
 
@@ -199,37 +281,63 @@ pub fn register_parsers(custom_parsers: &Vec<ParserConfig>, registry: &mut Parse
         )
         .unwrap();
 
+    let default_netscape_html_parser = NetscapeHtmlParser::new();
+    registry
+        .register(
+            default_netscape_html_parser.name().to_string(),
+            Box::new(default_netscape_html_parser),
+        )
+        .unwrap();
+
+    let default_chromium_parser = ChromiumBookmarksParser::new();
+    registry
+        .register(
+            default_chromium_parser.name().to_string(),
+            Box::new(default_chromium_parser),
+        )
+        .unwrap();
+
+    let default_firefox_parser = FirefoxPlacesParser::new();
+    registry
+        .register(
+            default_firefox_parser.name().to_string(),
+            Box::new(default_firefox_parser),
+        )
+        .unwrap();
+
+    for service in [
+        Service::Pocket,
+        Service::Pinboard,
+        Service::Raindrop,
+        Service::LinkdingShaarli,
+    ] {
+        let service_parser = ServiceExportParser::new(service);
+        registry
+            .register(service_parser.name().to_string(), Box::new(service_parser))
+            .unwrap();
+    }
+
     for parser_info in custom_parsers {
-        match parser_info.r#type.as_str() {
-            "python" => match PythonParser::new(parser_info) {
-                Ok(parser) => match registry.register(parser_info.name.clone(), Box::new(parser)) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        broadcast_info(
-                            "Parser Registration Error",
-                            &format!("Failed to register parser: {}", e),
-                            log::Level::Error,
-                            false,
-                        );
-                    }
-                },
+        match custom_parser(parser_info) {
+            Ok(parser) => match registry.register(parser_info.name.clone(), parser) {
+                Ok(_) => {}
                 Err(e) => {
                     broadcast_info(
-                        "Parser Error",
-                        &format!(
-                            "Failed to load Python parser from {}: {}",
-                            parser_info.path, e
-                        ),
+                        "Parser Registration Error",
+                        &format!("Failed to register parser: {}", e),
                         log::Level::Error,
                         false,
                     );
                 }
             },
-            _ => {
+            Err(e) => {
                 broadcast_info(
                     "Parser Error",
-                    &format!("Unsupported parser type: {}", parser_info.r#type),
-                    log::Level::Warn,
+                    &format!(
+                        "Failed to load {} parser from {}: {}",
+                        parser_info.r#type, parser_info.path, e
+                    ),
+                    log::Level::Error,
                     false,
                 );
             }
@@ -249,12 +357,17 @@ pub fn refresh_app_data(app_handle: &AppHandle) {
         .unwrap()
         .join("default.json");
 
+    // Each state is locked on its own, never while holding another one
     let app_data_binding = app_handle.state::<Mutex<AppData>>();
-    let registry_binding = app_handle.state::<Mutex<ParserRegistry>>();
-    let mut registry = registry_binding.lock().unwrap();
 
     let app_data_from_storage = read_app_data_from_storage(default_config_path, config_path);
 
+    {
+        let mut app_data = app_data_binding.lock().unwrap();
+        app_data.tracking_params = app_data_from_storage.tracking_params.clone();
+        app_data.duplicate_policy = app_data_from_storage.duplicate_policy;
+    }
+
     if app_data_from_storage.db_path.is_empty() {
         broadcast_info(
             "Database Path Error",
@@ -263,14 +376,29 @@ pub fn refresh_app_data(app_handle: &AppHandle) {
             false,
         );
     } else {
+        // Errors are reported by open_db and create_db, the current database is kept
         if Path::new(&app_data_from_storage.db_path).exists() {
-            open_db(app_data_binding, app_data_from_storage.db_path);
+            let _ = open_db(app_data_binding, app_data_from_storage.db_path);
         } else {
-            create_db(app_data_binding, app_data_from_storage.db_path);
+            let _ = create_db(app_data_binding, app_data_from_storage.db_path);
         }
     }
 
+    // Loading script parsers and exporters runs their interpreters, so the new registries
+    // are filled unlocked and only swapped in under the lock
+    let mut registry = ParserRegistry::new();
     register_parsers(&app_data_from_storage.custom_parsers, &mut registry);
+    *app_handle.state::<Mutex<ParserRegistry>>().lock().unwrap() = registry;
+
+    let mut exporter_registry = ExporterRegistry::new();
+    register_exporters(
+        &app_data_from_storage.custom_exporters,
+        &mut exporter_registry,
+    );
+    *app_handle
+        .state::<Mutex<ExporterRegistry>>()
+        .lock()
+        .unwrap() = exporter_registry;
 }
 
 pub fn exit_app(app_handle: &AppHandle) {
@@ -0,0 +1,279 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use time::OffsetDateTime;
+use wasmtime::{
+    Config, Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder, Trap, UpdateDeadline,
+};
+use wasmtime_wasi::pipe::MemoryOutputPipe;
+use wasmtime_wasi::preview1::{self, WasiP1Ctx};
+use wasmtime_wasi::{DirPerms, FilePerms, I32Exit, WasiCtxBuilder};
+
+use crate::custom_parsers::{
+    log_stderr, read_json_output, read_jsonl_output, stop_reason, CancelFlag, ParseProgress,
+    Parser, ParserSuccess, Watchdog,
+};
+use crate::parser_errors::ParserError;
+use crate::structs::{ParserConfig, ParserProtocol, ScriptOptions};
+
+// Version of the plugin ABI, bumped only for changes that break existing plugins.
+// Modules export `pcpocket_abi_version() -> i32` returning the version they were built for.
+const ABI_VERSION: i32 = 1;
+const ABI_VERSION_EXPORT: &str = "pcpocket_abi_version";
+// The input file is the only file in this read-only directory of the guest
+const GUEST_INPUT_DIR: &str = "/input";
+
+const DEFAULT_MEMORY_LIMIT_MB: usize = 256;
+const DEFAULT_FUEL: u64 = 10_000_000_000;
+// Output a plugin can write before its writes fail
+const OUTPUT_LIMIT: usize = 256 * 1024 * 1024;
+
+// Runs WebAssembly plugins built for WASI (wasm32-wasip1). Plugins have no network and can
+// only read the file being imported, so a shared parser can't touch anything else.
+pub struct WasmParser {
+    pub name: String,
+    r#type: String,
+    path: String,
+    supported_formats: Vec<String>,
+    options: ScriptOptions,
+    // Compiled once, every parse gets its own store
+    engine: Engine,
+    module: Module,
+}
+
+struct PluginState {
+    wasi: WasiP1Ctx,
+    limits: StoreLimits,
+}
+
+// Copy of the input file in a directory of its own, the only one the plugin gets.
+// The directory is removed when the copy is dropped.
+struct InputCopy {
+    directory: PathBuf,
+    file_name: String,
+}
+
+impl InputCopy {
+    fn new(input: &Path) -> std::io::Result<Self> {
+        let directory = std::env::temp_dir().join(format!(
+            "pcpocket-wasm-{}-{}",
+            std::process::id(),
+            OffsetDateTime::now_utc().unix_timestamp_nanos()
+        ));
+        fs::create_dir_all(&directory)?;
+
+        let file_name = input
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| "input".to_string());
+        let copy = InputCopy {
+            directory,
+            file_name,
+        };
+        fs::copy(input, copy.directory.join(&copy.file_name))?;
+
+        Ok(copy)
+    }
+}
+
+impl Drop for InputCopy {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.directory);
+    }
+}
+
+impl WasmParser {
+    pub fn new(parser_info: &ParserConfig) -> Result<Self, ParserError> {
+        if !Path::new(&parser_info.path).exists() {
+            return Err(ParserError::FileReadError(format!(
+                "Wasm module not found: {}",
+                parser_info.path
+            )));
+        }
+
+        let mut config = Config::new();
+        config.consume_fuel(true).epoch_interruption(true);
+        let engine = Engine::new(&config)?;
+        let module = Module::from_file(&engine, &parser_info.path)?;
+
+        for export in ["_start", ABI_VERSION_EXPORT] {
+            if module
+                .get_export(export)
+                .is_none_or(|export| export.func().is_none())
+            {
+                return Err(ParserError::WasmError(format!(
+                    "Module doesn't export the function {}",
+                    export
+                )));
+            }
+        }
+
+        Ok(Self {
+            name: parser_info.name.to_string(),
+            r#type: parser_info.r#type.to_string(),
+            path: parser_info.path.to_string(),
+            supported_formats: parser_info.supported_formats.to_vec(),
+            options: parser_info.script.clone(),
+            engine,
+            module,
+        })
+    }
+
+    // Instantiate the plugin, check its ABI version and run its main function.
+    // Returns the exit code, traps and limits are errors.
+    fn run(&self, store: &mut Store<PluginState>) -> wasmtime::Result<i32> {
+        let mut linker: Linker<PluginState> = Linker::new(&self.engine);
+        preview1::add_to_linker_sync(&mut linker, |state| &mut state.wasi)?;
+        let instance = linker.instantiate(&mut *store, &self.module)?;
+
+        let version = instance
+            .get_typed_func::<(), i32>(&mut *store, ABI_VERSION_EXPORT)?
+            .call(&mut *store, ())?;
+        if version != ABI_VERSION {
+            return Err(wasmtime::Error::msg(format!(
+                "Module was built for ABI version {}, this version of PcPocket supports {}",
+                version, ABI_VERSION
+            )));
+        }
+
+        let start = instance.get_typed_func::<(), ()>(&mut *store, "_start")?;
+        match start.call(&mut *store, ()) {
+            Ok(()) => Ok(0),
+            Err(e) => match e.downcast_ref::<I32Exit>() {
+                Some(exit) => Ok(exit.0),
+                None => Err(e),
+            },
+        }
+    }
+}
+
+impl Parser for WasmParser {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn supported_formats(&self) -> Vec<&str> {
+        self.supported_formats.iter().map(|s| s.as_str()).collect()
+    }
+
+    fn parse(&self, input_path: &str) -> Result<ParserSuccess, ParserError> {
+        self.parse_with_progress(input_path, &mut |_| {}, &CancelFlag::default())
+    }
+
+    // The plugin is called like a command: `<parser name> /input/<file name> [args]`,
+    // with the same output on stdout as Python parsers
+    fn parse_with_progress(
+        &self,
+        input_path: &str,
+        progress: &mut dyn FnMut(&ParseProgress),
+        cancel: &CancelFlag,
+    ) -> Result<ParserSuccess, ParserError> {
+        let input = InputCopy::new(Path::new(input_path))
+            .map_err(|e| ParserError::FileReadError(format!("Error reading file: {}", e)))?;
+        let guest_input = format!("{}/{}", GUEST_INPUT_DIR, input.file_name);
+
+        let stdout = MemoryOutputPipe::new(OUTPUT_LIMIT);
+        let stderr = MemoryOutputPipe::new(OUTPUT_LIMIT);
+        let mut wasi = WasiCtxBuilder::new();
+        wasi.arg(&self.name)
+            .arg(&guest_input)
+            .args(&self.options.args)
+            .stdout(stdout.clone())
+            .stderr(stderr.clone())
+            .preopened_dir(
+                &input.directory,
+                GUEST_INPUT_DIR,
+                DirPerms::READ,
+                FilePerms::READ,
+            )?;
+        for (key, value) in &self.options.env {
+            wasi.env(key, value);
+        }
+
+        let memory_limit = self
+            .options
+            .memory_limit_mb
+            .unwrap_or(DEFAULT_MEMORY_LIMIT_MB);
+        let mut store = Store::new(
+            &self.engine,
+            PluginState {
+                wasi: wasi.build_p1(),
+                limits: StoreLimitsBuilder::new()
+                    .memory_size(memory_limit * 1024 * 1024)
+                    .build(),
+            },
+        );
+        store.limiter(|state| &mut state.limits);
+        let fuel = self.options.instruction_limit.unwrap_or(DEFAULT_FUEL);
+        store.set_fuel(fuel)?;
+        // The epoch is shared by every parse with this engine, so a bump only interrupts
+        // the parse whose watchdog set its flag, the others carry on
+        let interrupted = Arc::new(AtomicBool::new(false));
+        store.set_epoch_deadline(1);
+        let store_interrupted = interrupted.clone();
+        store.epoch_deadline_callback(move |_| {
+            if store_interrupted.load(Ordering::SeqCst) {
+                Err(Trap::Interrupt.into())
+            } else {
+                Ok(UpdateDeadline::Continue(1))
+            }
+        });
+
+        let engine = self.engine.clone();
+        let watchdog = Watchdog::start(
+            self.options.timeout_secs.map(Duration::from_secs),
+            cancel,
+            move || {
+                interrupted.store(true, Ordering::SeqCst);
+                engine.increment_epoch();
+            },
+        );
+        let result = self.run(&mut store);
+        watchdog.stop();
+        let error = log_stderr(&format!("Parser {}", self.name), &stderr.contents()[..]);
+
+        // A module that finished just as the watchdog fired wasn't stopped
+        let trap = result
+            .as_ref()
+            .err()
+            .and_then(|e| e.downcast_ref::<Trap>().copied());
+        if trap == Some(Trap::Interrupt) {
+            return Err(ParserError::WasmError(format!(
+                "Module {}",
+                stop_reason(cancel, self.options.timeout_secs)
+            )));
+        }
+        if trap == Some(Trap::OutOfFuel) {
+            return Err(ParserError::WasmError(format!(
+                "Instruction limit of {} reached",
+                fuel
+            )));
+        }
+        let exit_code = result?;
+        if exit_code != 0 {
+            return Err(ParserError::WasmError(format!(
+                "Module exited with code {}: {}",
+                exit_code, error
+            )));
+        }
+
+        let output = stdout.contents();
+        match self.options.protocol.unwrap_or_default() {
+            ParserProtocol::Json => read_json_output(&output[..]),
+            ParserProtocol::Jsonl => read_jsonl_output(&output[..], progress),
+        }
+    }
+
+    fn info(&self) -> ParserConfig {
+        ParserConfig {
+            name: self.name.to_string(),
+            r#type: self.r#type.to_string(),
+            path: self.path.to_string(),
+            supported_formats: self.supported_formats.clone(),
+            script: self.options.clone(),
+        }
+    }
+}
@@ -13,8 +13,12 @@ function ImportForm({ setOpen }: { setOpen: (open: boolean) => void }) {
   const importBookmarks = useImportBookmarksMutation();
 
   useEffect(() => {
+    // Files without an extension (e.g. Chromium's "Bookmarks") are matched by name
+    const fileName = filePath.split(/[\\/]/).pop() ?? "";
     invoke("list_supported_parsers", {
-      requiredFormat: filePath.split(".").pop(),
+      requiredFormat: fileName.includes(".")
+        ? fileName.split(".").pop()
+        : fileName,
     }).then((parsers) => {
       setSupportedParsers(parsers as string[]);
     });
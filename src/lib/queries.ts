@@ -8,12 +8,14 @@ import { save, open } from "@tauri-apps/plugin-dialog";
 import {
   BookmarkGetQueryResponse,
   BookmarkMutationItem,
+  BookmarkQueryItem,
+  BookmarkStreamBatch,
   ParserConfigType,
 } from "@/types";
 import { useNavigate } from "@tanstack/react-router";
 import { Route } from "@/routes/main/bookmarks";
 import { getLinkPreview } from "link-preview-js";
-import { invoke } from "@tauri-apps/api/core";
+import { Channel, invoke } from "@tauri-apps/api/core";
 import { ColumnFiltersState, SortingState } from "@tanstack/react-table";
 
 export function useGetDbPathQuery() {
@@ -91,6 +93,59 @@ export function useGetBookmarksQuery(
   });
 }
 
+// Read every bookmark matching the filters in batches instead of one huge response.
+// Each batch is acknowledged once `onBatch` returns, which lets the backend send the next one.
+// Rejects when the stream fails or `signal` aborts it, which stops the backend as well.
+export async function streamBookmarks(
+  filters: ColumnFiltersState,
+  sort: SortingState,
+  onBatch: (bookmarks: BookmarkQueryItem[], totalCount: number) => void,
+  batchSize?: number,
+  signal?: AbortSignal,
+): Promise<void> {
+  return new Promise((resolve, reject) => {
+    if (signal?.aborted) {
+      reject(signal.reason);
+      return;
+    }
+
+    const channel = new Channel<BookmarkStreamBatch>();
+    channel.onmessage = (message) => {
+      if (message.error) {
+        reject(new Error(message.error));
+        return;
+      }
+      onBatch(message.bookmarks, message.totalCount);
+      if (message.done) {
+        resolve();
+        return;
+      }
+      invoke("ack_bookmark_stream", {
+        streamId: message.streamId,
+        batch: message.batch,
+      }).catch(reject);
+    };
+    const streamId = invoke<number>("stream_bookmarks", {
+      filters,
+      sort,
+      batchSize,
+      onBatch: channel,
+    });
+    streamId.catch(reject);
+
+    signal?.addEventListener(
+      "abort",
+      () => {
+        streamId
+          .then((id) => invoke("cancel_bookmark_stream", { streamId: id }))
+          .catch(() => {});
+        reject(signal.reason);
+      },
+      { once: true },
+    );
+  });
+}
+
 export function useGetAllTagsQuery() {
   return useQuery({
     queryKey: ["tags"],
@@ -58,6 +58,15 @@ export type BookmarkSelectItem = {
   tag_name?: string;
 };
 
+export type BookmarkStreamBatch = {
+  streamId: number;
+  batch: number;
+  bookmarks: BookmarkQueryItem[];
+  totalCount: number;
+  done: boolean;
+  error: string | null;
+};
+
 export type BookmarkGetQueryResponse = {
   bookmarks: BookmarkQueryItem[];
   totalCount: number;